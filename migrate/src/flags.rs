@@ -0,0 +1,57 @@
+// --name value, --name=value, and the bare switches below which never take a value
+const SWITCHES: &[&str] = &[
+    "yes",
+    "dry-run",
+    "resume",
+    "all-years",
+    "dump",
+    "schema-only",
+    "data-only",
+    "single-transaction",
+    "quick",
+    "skip-lock-tables",
+    "builtin",
+    "continue-on-error",
+    "force",
+];
+
+#[derive(Debug, Default)]
+pub struct Flags {
+    items: Vec<(String, Option<String>)>,
+}
+
+impl Flags {
+    // remove every --flag from args, leaving the positional ones in order
+    pub fn take(args:&mut Vec<String>)->Self {
+        let mut items = Vec::new();
+        let mut rest = Vec::with_capacity(args.len());
+        let mut iter = args.drain(..);
+        while let Some(arg) = iter.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                rest.push(arg);
+                continue;
+            };
+            if let Some((name, value)) = flag.split_once('=') {
+                items.push((name.to_string(), Some(value.to_string())));
+            } else if SWITCHES.contains(&flag) {
+                items.push((flag.to_string(), None));
+            } else {
+                items.push((flag.to_string(), iter.next()));
+            }
+        }
+        drop(iter);
+        *args = rest;
+        Self { items }
+    }
+
+    pub fn get(&self, name:&str)->Option<&str> {
+        self.items.iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .and_then(|(_, v)| v.as_deref())
+    }
+
+    pub fn has(&self, name:&str)->bool {
+        self.items.iter().any(|(n, _)| n == name)
+    }
+}
@@ -1,31 +1,62 @@
 use std::env::args;
-use util::TableRule;
-use util::{self, DatabaseEnv};
+use util::{TableHandle, TableHandleMut, TableRule};
+use util::{self, Approval, BackupEnv, ConfirmEnv, DatabaseEnv, DumpEnv, DumpOptions, LoadEnv, PanelEnv, TrashEnv};
+use util::dump::DumpMode;
+use util::plan::Plan;
+use util::history::{self, CountRun};
+use util::report::{OutputFormat, Row, TableReport};
+use util::export::{Compression, ExportFormat};
+use util::import::{ImportFormat, ImportOptions};
+use util::rowdiff::DiffFormat;
+use util::sqlload::{LoadOptions, OnError};
 use std::fs::File;
 use std::io::BufWriter;
 use std::io::Write;
 
+mod flags;
+use flags::Flags;
+
+// these commands are followed by a sub command before the cfg
+const SUBCOMMANDS: &[&str] = &[
+    "trash",
+    "plan",
+    "run",
+    "views",
+    "catalog",
+];
+
 fn main() {
-    let mut args = args();
-    args.next(); // ignore the self name
+    let mut args = args().skip(1).collect::<Vec<_>>(); // ignore the self name
+    let flags = Flags::take(&mut args);
+    let mut args = args.into_iter();
     let Some(cmd) = args.next() else {
         help();
         std::process::exit(0);
     };
+    let sub = if SUBCOMMANDS.contains(&cmd.as_str()) {
+        args.next().unwrap_or_default()
+    } else {
+        String::new()
+    };
     let cfg = args.next();
     let postfix = args.next().unwrap_or_default();
+    let rest = args.collect::<Vec<_>>();
 
     let env = util::load_panel_env(cfg);
     let env_ro = env.to_ro_dbenv();
     let env_rw = env.to_rw_dbenv();
     let rule = env.table_rule();
+    let approval = Approval {
+        yes: flags.has("yes"),
+        token: flags.get("confirm-token"),
+    };
 
     match cmd.as_str() {
         "dumpout" => {
-            dumpout(&env_ro,&rule, &postfix, &env.basedir);
+            dumpout(&env_ro,&rule, &postfix, &env.basedir, &env.dump, &flags);
         }
         "dumpin" => {
-            dumpin(&env_ro,&rule, &postfix, &env.basedir);
+            dumpin(&env_ro,&rule, &postfix, &env.basedir, &env.load, &flags);
         }
         "copy" => {
             copy(&env_rw, &rule, &postfix);
@@ -34,28 +65,145 @@ fn main() {
             zip(&env.basedir, &rule);
         }
         "nameadd" => {
+            guard_protected(&env.confirm, &rule.tables());
             add_postfix(&env_rw, &rule, &postfix);
         }
         "namendel" => {
+            guard_protected(&env.confirm, &postfix_tables(&rule, &postfix));
             remove_postfix(&env_rw, &rule, &postfix);
         }
         "take" => {
+            guard_protected(&env.confirm, &rule.tables());
+            guard_backups(&env_rw, &env.backup, &env.dump, &env.basedir, &rule, &rule.tables());
             take_to_postfix(&env_rw, &rule, &postfix);
         }
         "count" => {
-            count(&env_ro, &env.basedir, &rule, &postfix);
+            count(&env_ro, &env.basedir, &rule, &postfix, output_format(&flags), flags.get("diff"));
         }
         "empty" => {
-            empty(&env_ro, &env.basedir, &rule, &postfix);
+            empty(&env_ro, &env.basedir, &rule, &postfix, output_format(&flags));
+        }
+        "compare" => {
+            compare(&env_ro, &rule, &flags, output_format(&flags));
+        }
+        "diff-rows" => {
+            let Some(right) = rest.first() else {
+                help();
+                std::process::exit(2);
+            };
+            diff_rows(&env_ro, &postfix, right, &flags);
+        }
+        "schema-check" => {
+            let deviations = util::schemacheck::check(&env_ro, &rule, &postfix);
+            print_reports(output_format(&flags), &deviations);
+            if !deviations.is_empty() {
+                std::process::exit(1);
+            }
+        }
+        "schema-snapshot" => {
+            let dir = flags.get("dir").map(String::from)
+                .unwrap_or_else(|| util::snapshot::default_dir(&env.basedir, &env_ro.database));
+            let index = util::snapshot::take(&env_ro, &rule, &postfix, &dir).expect("Failed to write schema snapshot");
+            eprintln!("----- {} tables snapshotted at: {dir} -----", index.tables.len());
+        }
+        "schema-diff" => {
+            let Some(after) = rest.first() else {
+                help();
+                std::process::exit(2);
+            };
+            schema_diff(&env_ro, &rule, &postfix, after, flags.get("postfix"), output_format(&flags));
+        }
+        "alter" => {
+            alter(&env_rw, &env.basedir, &rule, &postfix, &flags);
+        }
+        "precreate" => {
+            precreate(&env_rw, &rule, &flags);
+        }
+        "consolidate" => {
+            consolidate(&env_rw, &rule, &flags, &env.confirm);
+        }
+        "split" => {
+            split(&env_rw, &flags);
+        }
+        "views" => {
+            views(&env_rw, &rule, &sub, &flags);
+        }
+        "prune" => {
+            prune(&env_rw, &env.basedir, &rule, &postfix, &flags, &env.confirm, &approval);
+        }
+        "export" => {
+            export(&env_ro, &env.basedir, &rule, &postfix, &flags);
+        }
+        "import" => {
+            import(&env_rw, &env.basedir, &rule, &postfix, &flags);
+        }
+        "catalog" => {
+            if sub != "search" {
+                eprintln!("Unknown catalog command: {sub}");
+                std::process::exit(2);
+            }
+            let pattern = if postfix.is_empty() { "*" } else { &postfix };
+            print_reports(output_format(&flags), &util::catalog::search(&env.basedir, pattern));
+        }
+        "restore" => {
+            restore(&env_rw, &env.basedir, &postfix, &flags, &env.load);
+        }
+        "stats" => {
+            stats(&env_ro, &rule, &postfix, output_format(&flags));
         }
         "drop" => {
-            drop_table(&env_rw, &postfix);
+            let tables = vec![postfix];
+            guard_backups(&env_rw, &env.backup, &env.dump, &env.basedir, &rule, &tables);
+            drop_tables(&env_rw, &tables, &env.confirm, &approval, &env.trash);
         }
         "drop-empty" => {
-            drop_empty_table(&env_rw, &rule, &postfix);
+            let tables = empty_tables(&env_rw, &rule, &postfix);
+            guard_backups(&env_rw, &env.backup, &env.dump, &env.basedir, &rule, &tables);
+            drop_tables(&env_rw, &tables, &env.confirm, &approval, &env.trash);
         }
         "batch-drop" => {
-            batch_drop_table(&env_rw, &rule, &postfix);
+            let tables = postfix_tables(&rule, &postfix);
+            guard_backups(&env_rw, &env.backup, &env.dump, &env.basedir, &rule, &tables);
+            drop_tables(&env_rw, &tables, &env.confirm, &approval, &env.trash);
+        }
+        "plan" => {
+            plan(&env_rw, &rule, &env.basedir, &sub, &postfix, &flags, &env.trash);
+        }
+        "apply" => {
+            let plan = Plan::load(&postfix);
+            let drift = plan.drift(&env_rw);
+            if !drift.is_empty() {
+                for d in &drift {
+                    eprintln!("{d}");
+                }
+                eprintln!("----- live state drifted from the plan, nothing applied -----");
+                std::process::exit(1);
+            }
+            let tables = plan.tables();
+            match plan.command.as_str() {
+                "take" => {
+                    guard_protected(&env.confirm, &tables);
+                    guard_backups(&env_rw, &env.backup, &env.dump, &env.basedir, &rule, &tables);
+                }
+                "batch-drop" => {
+                    guard_backups(&env_rw, &env.backup, &env.dump, &env.basedir, &rule, &tables);
+                    let action = if env.trash.enabled { "TRASH" } else { "DROP" };
+                    if !env.confirm.confirm_batch(&env_rw.database, action, &tables, &approval) {
+                        std::process::exit(1);
+                    }
+                }
+                other => {
+                    eprintln!("Unknown plan command: {other}");
+                    std::process::exit(2);
+                }
+            }
+            exit_on_failure(plan.apply(&env_rw));
+        }
+        "run" => {
+            run(&env, &sub, &postfix, &approval);
+        }
+        "trash" => {
+            trash(&env_rw, &env.trash, &sub, &postfix, &flags, &env.confirm, &approval);
         }
         _ => {
             eprintln!("Unknown command: {cmd}");
@@ -64,30 +212,83 @@ fn main() {
     }
 }
 
-fn dumpout(env:&DatabaseEnv, rule:&TableRule, postfix:&str, basedir:&str) {
-    let dump_out = {
-        |table: &str, year: &str, _i| {
-        let table = combine(table, postfix);
+// the flags win over [dump.names.{name}], which wins over [dump]
+fn dumpout(env:&DatabaseEnv, rule:&TableRule, postfix:&str, basedir:&str, dump:&DumpEnv, flags:&Flags) {
+    let overrides = dump_overrides(flags);
+    for (name, year, month) in rule.parts() {
+        let table = combine(&format!("{name}{year}{month}"), postfix);
         let outdir = format!("{basedir}/{year}");
-        util::dump_out(env,&table,&outdir);
-    }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
-        &dump_out,
-    ];
-    rule.for_each_tables( &handlers);
+        let options = overrides.or(&dump.options_for(name));
+        let status = util::dump_out_with(env, &table, &outdir, &options);
+        if status.success() && options.is_complete()
+            && let Err(err) = util::catalog::record_dump(basedir, name, year, month, &table) {
+            eprintln!("----- failed to catalog {table}: {err} -----");
+        }
+    }
 }
 
-fn dumpin(env:&DatabaseEnv, rule:&TableRule, postfix:&str, basedir:&str) {
-    let dump_out = {
-        |table: &str, year: &str, _i| {
-        let table = combine(table, postfix);
-        let sqlfile = format!("{basedir}/{year}/{table}.sql");
-        util::dump_in(env,&sqlfile);
-    }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
-        &dump_out,
-    ];
-    rule.for_each_tables(&handlers);
+fn dump_overrides(flags:&Flags)->DumpOptions {
+    let mode = match (flags.has("schema-only"), flags.has("data-only")) {
+        (true, true) => {
+            eprintln!("--schema-only and --data-only exclude each other");
+            std::process::exit(2);
+        }
+        (true, false) => Some(DumpMode::Schema),
+        (false, true) => Some(DumpMode::Data),
+        (false, false) => None,
+    };
+    let switch = |name:&str| flags.has(name).then_some(true);
+    DumpOptions {
+        filter: flags.get("where").map(String::from),
+        mode,
+        single_transaction: switch("single-transaction"),
+        quick: switch("quick"),
+        skip_lock_tables: switch("skip-lock-tables"),
+        extended_insert: flags.get("extended-insert").map(|n| n.parse().expect("--extended-insert must be a number")),
+        builtin: switch("builtin"),
+    }
+}
+
+// --as, --to-postfix and --to-database load through the builtin loader, which renames the table
+fn dumpin(env:&DatabaseEnv, rule:&TableRule, postfix:&str, basedir:&str, load:&LoadEnv, flags:&Flags) {
+    let retarget = ["as", "to-postfix", "to-database"].iter().any(|f| flags.get(f).is_some());
+    if flags.get("as").is_some() && rule.tables().len() != 1 {
+        eprintln!("--as needs a rule matching exactly one table, use --to-postfix for more");
+        std::process::exit(2);
+    }
+    if !load.builtin && !flags.has("builtin") && !retarget {
+        let dump_in = {
+            |table: &str, year: &str, _i| {
+            let table = combine(table, postfix);
+            let sqlfile = format!("{basedir}/{year}/{table}.sql");
+            util::dump_in(env,&sqlfile);
+        }};
+        let handlers: Vec<TableHandle> = vec![
+            &dump_in,
+        ];
+        rule.for_each_tables(&handlers);
+        return;
+    }
+    let options = LoadOptions {
+        on_error: if flags.has("continue-on-error") { OnError::Continue } else { load.on_error },
+        target: None,
+    };
+    let reports = rule.parts().into_iter()
+        .map(|(name, year, month)| {
+            let table = combine(&format!("{name}{year}{month}"), postfix);
+            let sqlfile = format!("{basedir}/{year}/{table}.sql");
+            if !retarget {
+                return util::sqlload::load_file(env, &sqlfile, &options);
+            }
+            let target = flags.get("as").map(String::from)
+                .unwrap_or_else(|| combine(&table, flags.get("to-postfix").unwrap_or_default()));
+            util::sqlload::restore(env, &sqlfile, &table, flags.get("to-database"), &target, flags.has("force"), options.on_error)
+        })
+        .collect::<Vec<_>>();
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| r.status != "loaded") {
+        std::process::exit(1);
+    }
 }
 
 fn copy(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
@@ -95,9 +296,9 @@ fn copy(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
         // if we remove the type &str, it will not work, grammer error WHY ???
         |table: &str, _year: &str, _i| {
         let table_new = combine(table, postfix);
-        util::copy(&env_rw, &table, &table_new);
+        util::copy(env_rw, table, &table_new);
     }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
+    let handlers: Vec<TableHandle> = vec![
         &copy,
     ];
     // why does work when we use static [] ?????
@@ -108,15 +309,22 @@ fn copy(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
 }
 
 fn zip(basedir:&str, rule:&TableRule) {
-    rule.for_each_name(&basedir,util::zip);
+    rule.for_each_name(basedir, |basedir, year, name| {
+        let status = util::zip(basedir, year, name);
+        if status.success()
+            && let Err(err) = util::catalog::record_zip(basedir, year, name) {
+            eprintln!("----- failed to catalog {name}{year}.zip: {err} -----");
+        }
+        status
+    });
 }
 
 fn add_postfix(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
     let rename = {
         |table: &str, _year: &str,_i| {
-        util::add_postfix(&env_rw, &table, postfix);
+        util::add_postfix(env_rw, table, postfix);
     }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
+    let handlers: Vec<TableHandle> = vec![
         &rename,
     ];
     // why does work when we use static [] ?????
@@ -130,9 +338,9 @@ fn remove_postfix(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
     let rename = {
         |table: &str, _year: &str, _i| {
         let table = combine(table, postfix);
-        util::remove_postfix(&env_rw, &table,postfix);
+        util::remove_postfix(env_rw, &table,postfix);
     }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
+    let handlers: Vec<TableHandle> = vec![
         &rename,
     ];
     // why does work when we use static [] ?????
@@ -145,15 +353,15 @@ fn remove_postfix(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
 fn take_to_postfix(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
     let rename = {
         |table: &str, _year: &str, _i| {
-        util::add_postfix(&env_rw,table, postfix);
+        util::add_postfix(env_rw,table, postfix);
     }};
     let create = {
         |table: &str, _year: &str, _i| {
         let src_table = &combine(table, postfix);
         let empty_table = &table;
-        util::create_empty(&env_rw, src_table, empty_table);
+        util::create_empty(env_rw, src_table, empty_table);
     }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
+    let handlers: Vec<TableHandle> = vec![
         &rename,
         &create,
     ];
@@ -164,109 +372,491 @@ fn take_to_postfix(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
     rule.for_each_tables(&handlers);
 }
 
-fn empty(env_ro:&DatabaseEnv, basedir:&str,rule:&TableRule, postfix:&str) {
+fn empty(env_ro:&DatabaseEnv, basedir:&str,rule:&TableRule, postfix:&str, format:OutputFormat) {
     let countpath = format!("{basedir}/{}-empty{postfix}.txt",env_ro.database);
-    eprintln!("----- empty file is at: {countpath} -----");
-    let file = File::create(countpath).unwrap();
-    let mut writer = BufWriter::new(file);
-    let mut content = Vec::new();
-    let mut handle  = {
-        |table: &str, _ext: &str| {
-        let table = combine(table, postfix);
-        let is_empty = util::is_empty(&env_ro, &table);
-        let out = if is_empty {b"1"} else {b"0"};
-        println!("{table} : {is_empty}");
-        let content_len = table.as_bytes().len() + 1 + out.len() + 1;
-        content.clear();
-        content.reserve(content_len);
-        content.extend_from_slice(table.as_bytes());
-        content.extend_from_slice(b" ");
-        content.extend_from_slice(out);
-        content.extend_from_slice(b"\n");
-        writer.write_all(&content).unwrap();
-    }};
-    let mut handlers: Vec<&mut dyn FnMut(&str, &str)> = vec![
-        &mut handle,
-    ];
-    // why does work when we use static [] ?????
-    // let handlers = [
-    //     &rename,
-    // ];
-    rule.for_each_tables_mut(&mut handlers);
-    writer.flush().unwrap();
+    let reports = reports(env_ro, rule, postfix, false, true);
+    write_txt(&countpath, &reports, |r| match r.empty {
+        Some(true) => "1".into(),
+        Some(false) => "0".into(),
+        None => String::new(),
+    });
+    print_reports(format, &reports);
 }
 
-fn count(env_ro:&DatabaseEnv, basedir:&str,rule:&TableRule, postfix:&str) {
+fn count(env_ro:&DatabaseEnv, basedir:&str,rule:&TableRule, postfix:&str, format:OutputFormat, diff:Option<&str>) {
     let countpath = format!("{basedir}/{}-count{postfix}.txt",env_ro.database);
-    eprintln!("----- count file is at: {countpath} -----");
-    let file = File::create(countpath).unwrap();
+    let historypath = history::history_path(basedir, &env_ro.database);
+    let runs = history::load(&historypath);
+    // resolved before this run is appended, so `last` is the previous one
+    let before = diff.map(|source| history::resolve(source, &runs, postfix).unwrap_or_else(|| {
+        eprintln!("No count run or file for --diff: {source}");
+        std::process::exit(2);
+    }));
+    let reports = reports(env_ro, rule, postfix, true, false);
+    write_txt(&countpath, &reports, |r| r.rows.map(|rows| rows.to_string()).unwrap_or_default());
+    let run = CountRun::new(&util::clock::now_stamp(), &env_ro.database, postfix, &reports);
+    history::append(&historypath, &run).expect("Failed to append count history");
+    eprintln!("----- count run {} is recorded in: {historypath} -----", run.run);
+    match before {
+        Some(before) => {
+            let diff = history::diff(&before, &run.counts());
+            print_reports(format, &diff);
+        }
+        None => print_reports(format, &reports),
+    }
+}
+
+fn compare(env_ro:&DatabaseEnv, rule:&TableRule, flags:&Flags, format:OutputFormat) {
+    let left_postfix = flags.get("left-postfix").unwrap_or_default();
+    let right_postfix = flags.get("right-postfix").unwrap_or_default();
+    // the right side is this database unless another cfg is given
+    let right_env = flags.get("right-cfg").map(|cfg| util::load_panel_env(Some(cfg.to_string())));
+    let right_ro = right_env.as_ref().map_or_else(|| env_ro.clone(), |env| env.to_ro_dbenv());
+    let rows = util::compare::compare(env_ro, left_postfix, &right_ro, right_postfix, rule);
+    print_reports(format, &rows);
+    let differ = rows.iter().filter(|r| !r.same()).count();
+    if differ > 0 {
+        eprintln!("----- {differ} of {} tables differ -----", rows.len());
+        std::process::exit(1);
+    }
+}
+
+// rows per chunk, at least one
+// --chunk, --batch and the like, a usage error unless a number above 0
+fn positive_flag(flags:&Flags, name:&str, default:usize)->usize {
+    let Some(value) = flags.get(name) else {
+        return default;
+    };
+    match value.parse() {
+        Ok(n) if n > 0 => n,
+        _ => {
+            eprintln!("Invalid --{name}: {value}, expected a number above 0");
+            std::process::exit(2);
+        }
+    }
+}
+
+fn diff_rows(env_ro:&DatabaseEnv, left:&str, right:&str, flags:&Flags) {
+    let format = flags.get("format").unwrap_or("sql");
+    let Some(format) = DiffFormat::parse(format) else {
+        eprintln!("Invalid --format: {format}, expected sql|csv");
+        std::process::exit(2);
+    };
+    let chunk = positive_flag(flags, "chunk", 10000);
+    let diff = util::rowdiff::diff_rows(env_ro, left, right, chunk).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+    match flags.get("out") {
+        Some(path) => {
+            let mut writer = BufWriter::new(File::create(path).unwrap());
+            util::rowdiff::write_diff(env_ro, left, right, &diff, format, &mut writer).unwrap();
+            writer.flush().unwrap();
+            eprintln!("----- row diff is at: {path} -----");
+        }
+        None => {
+            let stdout = std::io::stdout();
+            let mut out = stdout.lock();
+            util::rowdiff::write_diff(env_ro, left, right, &diff, format, &mut out).unwrap();
+            out.flush().unwrap();
+        }
+    }
+    if !diff.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn alter(env_rw:&DatabaseEnv, basedir:&str, rule:&TableRule, postfix:&str, flags:&Flags) {
+    let Some(sql) = flags.get("sql") else {
+        eprintln!("alter needs --sql, the part after ALTER TABLE <table>");
+        std::process::exit(2);
+    };
+    let tables = postfix_tables(rule, postfix);
+    let progress = util::alter::progress_path(basedir, &env_rw.database, sql);
+    let reports = util::alter::alter(env_rw, &tables, sql, &progress, flags.has("resume"), flags.has("dry-run"));
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| r.status == "failed") {
+        eprintln!("----- stopped, run again with --resume once fixed: {progress} -----");
+        std::process::exit(1);
+    }
+}
+
+// the cfg is the only positional, the months come from the clock
+fn precreate(env_rw:&DatabaseEnv, rule:&TableRule, flags:&Flags) {
+    let ahead = positive_flag(flags, "ahead", 1);
+    let reports = util::precreate::precreate(env_rw, &rule.names, ahead, flags.get("template"));
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| r.status == "failed" || r.status == "no template") {
+        std::process::exit(1);
+    }
+}
+
+fn consolidate(env_rw:&DatabaseEnv, rule:&TableRule, flags:&Flags, confirm:&ConfirmEnv) {
+    let Some(year) = flags.get("year") else {
+        eprintln!("consolidate needs --year, e.g. --year 17");
+        std::process::exit(2);
+    };
+    let postfix = flags.get("rename-postfix");
+    if postfix.is_some() {
+        let tables = rule.names.iter()
+            .flat_map(|name| rule.months.iter().map(move |month| format!("{name}{year}{month}")))
+            .collect::<Vec<_>>();
+        guard_protected(confirm, &tables);
+    }
+    let reports = util::consolidate::consolidate(env_rw, rule, year, flags.get("partition-column"), postfix);
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| r.status != "copied") {
+        std::process::exit(1);
+    }
+}
+
+// the monthly tables are named {name}yymm, name defaults to the source without its trailing digits
+fn split(env_rw:&DatabaseEnv, flags:&Flags) {
+    let (Some(source), Some(column)) = (flags.get("source"), flags.get("date-column")) else {
+        eprintln!("split needs --source <table> and --date-column <col>");
+        std::process::exit(2);
+    };
+    let name = flags.get("name").unwrap_or_else(|| source.trim_end_matches(|c:char| c.is_ascii_digit()));
+    let chunk = positive_flag(flags, "chunk", 10000);
+    let reports = util::split::split(env_rw, source, column, name, chunk).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(2);
+    });
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| r.status != "moved") {
+        std::process::exit(1);
+    }
+}
+
+// run again after take or drop changed which monthly tables exist
+fn views(env_rw:&DatabaseEnv, rule:&TableRule, sub:&str, flags:&Flags) {
+    if sub != "create" {
+        eprintln!("Unknown views command: {sub}");
+        std::process::exit(2);
+    }
+    let reports = util::views::create(env_rw, rule, flags.has("all-years"));
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| r.status == "failed") {
+        std::process::exit(1);
+    }
+}
+
+fn prune(env_rw:&DatabaseEnv, basedir:&str, rule:&TableRule, postfix:&str, flags:&Flags, confirm:&ConfirmEnv, approval:&Approval) {
+    let Some(predicate) = flags.get("where") else {
+        eprintln!("prune needs --where <predicate>");
+        std::process::exit(2);
+    };
+    let duration = |name:&str| flags.get(name).map(|d| util::clock::parse_duration(d).unwrap_or_else(|| {
+        eprintln!("Invalid --{name}: {d}");
+        std::process::exit(2);
+    }));
+    let dumpdir = format!("{basedir}/prune");
+    let options = util::prune::PruneOptions {
+        predicate,
+        chunk: positive_flag(flags, "chunk", 10000),
+        sleep: std::time::Duration::from_secs(duration("sleep").unwrap_or(0)),
+        max_runtime: duration("max-runtime").map(std::time::Duration::from_secs),
+        dumpdir: flags.has("dump").then_some(dumpdir.as_str()),
+    };
+    let tables = postfix_tables(rule, postfix);
+    if !confirm.confirm_batch(&env_rw.database, "PRUNE", &tables, approval) {
+        std::process::exit(1);
+    }
+    let reports = util::prune::prune(env_rw, &tables, &options);
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| !["pruned", "missing"].contains(&r.status.as_str())) {
+        std::process::exit(1);
+    }
+}
+
+// every table goes to {basedir}/{year}/{table}.{ext}, listed in {basedir}/{year}/manifest.jsonl
+fn export(env_ro:&DatabaseEnv, basedir:&str, rule:&TableRule, postfix:&str, flags:&Flags) {
+    let format = flags.get("format").unwrap_or("csv");
+    let Some(format) = ExportFormat::parse(format) else {
+        eprintln!("Invalid --format: {format}, expected csv|parquet|jsonl");
+        std::process::exit(2);
+    };
+    let compress = flags.get("compress").unwrap_or("none");
+    let Some(compression) = Compression::parse(compress) else {
+        eprintln!("Invalid --compress: {compress}, expected none|gzip|zstd");
+        std::process::exit(2);
+    };
+    if format == ExportFormat::Parquet && let Err(err) = util::export::require_duckdb() {
+        eprintln!("{err}");
+        std::process::exit(2);
+    }
+    let mut entries = Vec::new();
+    let mut failed = 0;
+    for (name, year, month) in rule.parts() {
+        let table = combine(&format!("{name}{year}{month}"), postfix);
+        let outdir = format!("{basedir}/{year}");
+        match util::export::export(env_ro, &table, &outdir, format, compression) {
+            Ok(entry) => entries.push(entry),
+            Err(err) => {
+                eprintln!("----- export of {table} failed: {err} -----");
+                failed += 1;
+            }
+        }
+    }
+    print_reports(output_format(flags), &entries);
+    if failed > 0 {
+        std::process::exit(1);
+    }
+}
+
+// {basedir}/{year}/{name}{year}{month}.{ext} is loaded into {name}{year}{month}{postfix}
+fn import(env_rw:&DatabaseEnv, basedir:&str, rule:&TableRule, postfix:&str, flags:&Flags) {
+    let format = flags.get("format").unwrap_or("csv");
+    let Some(format) = ImportFormat::parse(format) else {
+        eprintln!("Invalid --format: {format}, expected csv|parquet");
+        std::process::exit(2);
+    };
+    if format == ImportFormat::Parquet && let Err(err) = util::export::require_duckdb() {
+        eprintln!("{err}");
+        std::process::exit(2);
+    }
+    let options = ImportOptions {
+        template: flags.get("template"),
+        batch: positive_flag(flags, "batch", 1000),
+        force: flags.has("force"),
+    };
+    let reports = rule.parts().into_iter()
+        .map(|(name, year, month)| {
+            let table = format!("{name}{year}{month}");
+            let path = format!("{basedir}/{year}/{table}.{}", format.ext());
+            util::import::import(env_rw, &path, format, &combine(&table, postfix), name, &options)
+        })
+        .collect::<Vec<_>>();
+    print_reports(output_format(flags), &reports);
+    if reports.iter().any(|r| r.status != "imported" && r.status != "no file") {
+        std::process::exit(1);
+    }
+}
+
+// the newest cataloged dump of the table, taken out of its zip when the .sql is gone
+fn restore(env_rw:&DatabaseEnv, basedir:&str, table:&str, flags:&Flags, load:&LoadEnv) {
+    let Some(entry) = util::catalog::find(basedir, table) else {
+        eprintln!("{table} is not in the catalog: {}", util::catalog::catalog_path(basedir));
+        std::process::exit(1);
+    };
+    let path = util::catalog::fetch(basedir, &entry).unwrap_or_else(|err| {
+        eprintln!("{err}");
+        std::process::exit(1);
+    });
+    let target = flags.get("as").map(String::from)
+        .unwrap_or_else(|| combine(table, flags.get("to-postfix").unwrap_or_default()));
+    let on_error = if flags.has("continue-on-error") { OnError::Continue } else { load.on_error };
+    let report = util::sqlload::restore(env_rw, &path, table, flags.get("to-database"), &target, flags.has("force"), on_error);
+    let loaded = report.status == "loaded";
+    print_reports(output_format(flags), &[report]);
+    if !loaded {
+        std::process::exit(1);
+    }
+}
+
+// after is another snapshot dir or `live`, the live tables of the rule with --postfix,
+// by default the postfix the before snapshot was taken with
+fn schema_diff(env_ro:&DatabaseEnv, rule:&TableRule, before:&str, after:&str, postfix:Option<&str>, format:OutputFormat) {
+    let load = |dir:&str| util::snapshot::load(dir).unwrap_or_else(|err| {
+        eprintln!("Failed to load schema snapshot {dir}: {err}");
+        std::process::exit(2);
+    });
+    let dir = before;
+    let before = load(dir);
+    let after = match after {
+        "live" => {
+            let postfix = postfix.map(String::from).unwrap_or_else(|| {
+                util::snapshot::read_index(dir).map(|index| index.postfix).unwrap_or_default()
+            });
+            util::snapshot::live(env_ro, rule, &postfix)
+        }
+        dir => load(dir),
+    };
+    let rows = util::snapshot::diff(&before, &after);
+    print_reports(format, &rows);
+    if !rows.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+fn stats(env_ro:&DatabaseEnv, rule:&TableRule, postfix:&str, format:OutputFormat) {
+    let stats = util::stats::collect(env_ro, rule, postfix);
+    let totals = util::stats::totals(&stats);
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    util::stats::write_stats(format, &stats, &totals, &mut out).unwrap();
+    out.flush().unwrap();
+}
+
+fn reports(env_ro:&DatabaseEnv, rule:&TableRule, postfix:&str, rows:bool, empty:bool)->Vec<TableReport> {
+    rule.parts().into_iter()
+        .map(|parts| {
+            let (name, year, month) = parts;
+            let table = combine(&format!("{name}{year}{month}"), postfix);
+            TableReport::probe(env_ro, parts, &table, rows, empty)
+        })
+        .collect()
+}
+
+// the `table value` lines kept for the scripts reading them
+fn write_txt(path:&str, reports:&[TableReport], value:impl Fn(&TableReport)->String) {
+    eprintln!("----- file is at: {path} -----");
+    let file = File::create(path).unwrap();
     let mut writer = BufWriter::new(file);
-    let mut content = Vec::new();
-    let mut count = {
-        |table: &str, _ext: &str| {
-        let table = combine(table, postfix);
-        let out = util::count(&env_ro, &table);
-        let out = out.1.trim_end();
-        println!("{table} : {out}");
-        let content_len = table.as_bytes().len() + 1 + out.as_bytes().len() + 1;
-        content.clear();
-        content.reserve(content_len);
-        content.extend_from_slice(table.as_bytes());
-        content.extend_from_slice(b" ");
-        content.extend_from_slice(out.as_bytes());
-        content.extend_from_slice(b"\n");
-        writer.write_all(&content).unwrap();
-    }};
-    let mut handlers: Vec<&mut dyn FnMut(&str, &str)> = vec![
-        &mut count,
-    ];
-    // why does work when we use static [] ?????
-    // let handlers = [
-    //     &rename,
-    // ];
-    rule.for_each_tables_mut(&mut handlers);
+    for r in reports {
+        writeln!(writer, "{} {}", r.table, value(r)).unwrap();
+    }
     writer.flush().unwrap();
 }
 
-fn drop_table(env_rw:&DatabaseEnv, table:&str) {
-    util::drop_with_confirm(&env_rw,table, util::DropConfirmEnum::DropFist);
+fn print_reports<T:Row>(format:OutputFormat, reports:&[T]) {
+    let stdout = std::io::stdout();
+    let mut out = stdout.lock();
+    util::report::write_reports(format, reports, &mut out).unwrap();
+    out.flush().unwrap();
 }
 
-fn drop_empty_table(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
-    let handle = {
-        |table: &str, _year: &str, _i: usize| {
+fn output_format(flags:&Flags)->OutputFormat {
+    let output = flags.get("output").unwrap_or("table");
+    OutputFormat::parse(output).unwrap_or_else(|| {
+        eprintln!("Invalid --output: {output}, expected json|csv|table");
+        std::process::exit(2);
+    })
+}
+
+fn drop_tables(env_rw:&DatabaseEnv, tables:&[String], confirm:&ConfirmEnv, approval:&Approval, trash:&TrashEnv) {
+    exit_on_failure(util::drop_with_confirm(env_rw, tables, confirm, approval, trash));
+}
+
+fn empty_tables(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str)->Vec<String> {
+    let mut tables = Vec::new();
+    let mut handle = {
+        |table: &str, _year: &str| {
         let table = combine(table, postfix);
-        if util::is_empty(&env_rw,&table) {
+        if util::is_empty(env_rw,&table) {
             println!("----- {table} is empty, and drop.");
-            util::drop_with_confirm(&env_rw,&table,util::DropConfirmEnum::DropWarn);
+            tables.push(table);
         }
     }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
-        &handle,
+    let mut handlers: Vec<TableHandleMut> = vec![
+        &mut handle,
     ];
-    // why does work when we use static [] ?????
-    // let handlers = [
-    //     &rename,
-    // ];
-    rule.for_each_tables(&handlers);
+    rule.for_each_tables_mut(&mut handlers);
+    tables
 }
 
-fn batch_drop_table(env_rw:&DatabaseEnv, rule:&TableRule, postfix:&str) {
-    let handle = {
-        |table: &str, _year: &str, i:usize| {
-        let table = combine(table, postfix);
-        println!("----- {table} selected, and drop.");
-        let confirm = util::DropConfirmEnum::from_usize(i);
-        util::drop_with_confirm(&env_rw,&table,confirm);
-    }};
-    let handlers: Vec<&dyn Fn(&str, &str, usize)> = vec![
-        &handle,
-    ];
-    // why does work when we use static [] ?????
-    // let handlers = [
-    //     &rename,
-    // ];
-    rule.for_each_tables(&handlers);
+fn postfix_tables(rule:&TableRule, postfix:&str)->Vec<String> {
+    rule.tables().iter()
+        .map(|table| combine(table, postfix))
+        .collect()
+}
+
+// nothing is destroyed unless every table of the batch has a verified dump
+fn guard_backups(env_rw:&DatabaseEnv, backup:&BackupEnv, dump:&DumpEnv, basedir:&str, rule:&TableRule, tables:&[String]) {
+    let mut refused = Vec::new();
+    for table in tables {
+        let outdir = util::backup::locate(basedir, table)
+            .or_else(|| rule.year_of(table).map(|year| format!("{basedir}/{year}")))
+            .unwrap_or_else(|| basedir.to_string());
+        let options = rule.name_of(table).map(|name| dump.options_for(name)).unwrap_or_default();
+        if !util::backup::ensure(env_rw, backup, table, &outdir, &options) {
+            refused.push(table.as_str());
+        }
+    }
+    if !refused.is_empty() {
+        eprintln!("----- no verified dump, nothing destroyed: {} -----", refused.join(" "));
+        std::process::exit(1);
+    }
+}
+
+// renames must not touch protected tables either
+fn guard_protected(confirm:&ConfirmEnv, tables:&[String]) {
+    let protected = confirm.protected_of(tables);
+    if !protected.is_empty() {
+        eprintln!("----- protected tables can not be renamed: {} -----", protected.join(" "));
+        std::process::exit(1);
+    }
+}
+
+fn exit_on_failure(status:std::process::ExitStatus) {
+    if !status.success() {
+        std::process::exit(status.code().unwrap_or(1));
+    }
+}
+
+fn plan(env_rw:&DatabaseEnv, rule:&TableRule, basedir:&str, sub:&str, postfix:&str, flags:&Flags, trash:&TrashEnv) {
+    let plan = match sub {
+        "take" => Plan::take(env_rw, rule, postfix),
+        "batch-drop" => Plan::batch_drop(env_rw, rule, postfix, trash),
+        _ => {
+            eprintln!("Unknown plan command: {sub}");
+            std::process::exit(2);
+        }
+    };
+    for step in &plan.steps {
+        println!("{} : {}", step.table, step.expect);
+        for sql in &step.sql {
+            println!("    {sql}");
+        }
+    }
+    let planfile = flags.get("out").map(String::from).unwrap_or_else(|| {
+        format!("{basedir}/{}-plan-{sub}{postfix}-{}.toml", env_rw.database, plan.created_at)
+    });
+    plan.save(&planfile).expect("Failed to write plan file");
+    eprintln!("----- plan file is at: {planfile} -----");
+}
+
+fn run(env:&PanelEnv, name:&str, postfix:&str, approval:&Approval) {
+    let Some(pipeline) = env.pipelines.get(name) else {
+        eprintln!("Unknown pipeline: {name}");
+        std::process::exit(2);
+    };
+    if let Err(err) = util::pipeline::check(pipeline) {
+        eprintln!("pipeline {name}: {err}");
+        std::process::exit(2);
+    }
+    let outcomes = util::pipeline::run(env, pipeline, postfix, approval);
+    let mut failed = 0;
+    for outcome in &outcomes {
+        match &outcome.failed {
+            None => println!("{} : ok", outcome.table),
+            Some((step, reason)) => {
+                failed += 1;
+                println!("{} : failed at {step}, {reason}", outcome.table);
+            }
+        }
+    }
+    if failed > 0 {
+        eprintln!("----- {failed} of {} tables failed -----", outcomes.len());
+        std::process::exit(1);
+    }
+}
+
+fn trash(env_rw:&DatabaseEnv, trash:&TrashEnv, sub:&str, table:&str, flags:&Flags, confirm:&ConfirmEnv, approval:&Approval) {
+    let status = match sub {
+        "ls" => {
+            for e in util::trash::list(env_rw, trash) {
+                println!("{}\t{}\t{}\t{}", e.table, e.stamp, e.rows, e.trashed);
+            }
+            return;
+        }
+        "restore" => {
+            util::trash::restore(env_rw, trash, table)
+        }
+        "purge" => {
+            let older_than = flags.get("older-than").unwrap_or("30d");
+            let Some(older_than) = util::clock::parse_duration(older_than) else {
+                eprintln!("Invalid --older-than: {older_than}");
+                std::process::exit(2);
+            };
+            util::trash::purge(env_rw, trash, older_than, confirm, approval)
+        }
+        _ => {
+            eprintln!("Unknown trash command: {sub}");
+            std::process::exit(2);
+        }
+    };
+    exit_on_failure(status);
 }
 
 fn combine(table:&str, postfix:&str)->String {
@@ -278,4 +868,31 @@ fn combine(table:&str, postfix:&str)->String {
 
 fn help() {
     eprintln!(r"migrate copy|take|dumpout|dumpin|zip|nameadd|namendel|count|empty|drop-empty cfg <postfix>");
+    eprintln!(r"migrate dumpout cfg <postfix> [--where pred] [--schema-only|--data-only] [--single-transaction] [--quick] [--skip-lock-tables] [--extended-insert bytes] [--builtin]");
+    eprintln!(r"  --builtin writes the dump without mysqldump, the mysql client is still required");
+    eprintln!(r"migrate dumpin cfg <postfix> [--builtin] [--continue-on-error] [--output json|csv|table]");
+    eprintln!(r"migrate dumpin cfg <postfix> [--as name | --to-postfix _restore] [--to-database scratch] [--force]");
+    eprintln!(r"migrate count|empty|stats|schema-check cfg <postfix> [--output json|csv|table]");
+    eprintln!(r"migrate count cfg <postfix> --diff last|<run>|<file>");
+    eprintln!(r"migrate diff-rows cfg <left> <right> [--format sql|csv] [--chunk 10000] [--out file]");
+    eprintln!(r"migrate alter cfg <postfix> --sql 'ADD COLUMN foo INT NULL' [--dry-run] [--resume] [--output json|csv|table]");
+    eprintln!(r"migrate precreate cfg [--ahead 1] [--template table] [--output json|csv|table]");
+    eprintln!(r"migrate consolidate cfg --year 17 [--partition-column col] [--rename-postfix postfix] [--output json|csv|table]");
+    eprintln!(r"migrate split cfg --source table --date-column col [--name name] [--chunk 10000] [--output json|csv|table]");
+    eprintln!(r"migrate views create cfg [--all-years] [--output json|csv|table]");
+    eprintln!(r"migrate prune cfg <postfix> --where <predicate> [--chunk 10000] [--sleep 1s] [--max-runtime 1h] [--dump] [--yes]");
+    eprintln!(r"migrate export cfg <postfix> [--format csv|parquet|jsonl] [--compress none|gzip|zstd] [--output json|csv|table]");
+    eprintln!(r"  --format parquet needs the duckdb CLI on PATH");
+    eprintln!(r"migrate import cfg <postfix> [--format csv|parquet] [--template table] [--batch 1000] [--force] [--output json|csv|table]");
+    eprintln!(r"  --format parquet needs the duckdb CLI on PATH");
+    eprintln!(r"migrate catalog search cfg <pattern> [--output json|csv|table]");
+    eprintln!(r"migrate restore cfg <table> [--as name | --to-postfix _restore] [--to-database scratch] [--force]");
+    eprintln!(r"migrate schema-snapshot cfg <postfix> [--dir dir]");
+    eprintln!(r"migrate schema-diff cfg <snapA> <snapB|live> [--postfix postfix] [--output json|csv|table]");
+    eprintln!(r"migrate compare cfg --left-postfix X --right-postfix Y [--right-cfg cfg2] [--output json|csv|table]");
+    eprintln!(r"migrate drop|batch-drop cfg <table|postfix> [--yes --confirm-token <token>]");
+    eprintln!(r"migrate trash ls|restore|purge cfg <table> [--older-than 30d]");
+    eprintln!(r"migrate plan take|batch-drop cfg <postfix> [--out planfile]");
+    eprintln!(r"migrate apply cfg <planfile>");
+    eprintln!(r"migrate run <pipeline> cfg <postfix>");
 }
@@ -0,0 +1,227 @@
+use std::io::Write;
+
+use super::{exe_sql, exe_sql_with_output, table_exists};
+use super::confirm::batch_token;
+use super::panelenv::DatabaseEnv;
+use super::report::Row;
+use super::schema::columns;
+
+// what a clause of the ALTER TABLE leaves behind, Unknown when it can not be checked
+#[derive(Debug, PartialEq)]
+pub enum Check {
+    ColumnPresent(String),
+    ColumnAbsent(String),
+    IndexPresent(String),
+    IndexAbsent(String),
+    Unknown,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct AlterReport {
+    pub table: String,
+    pub status: String,
+    pub reason: String,
+}
+
+impl Row for AlterReport {
+    const COLUMNS: &'static [&'static str] = &["table", "status", "reason"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.table.clone(),
+            self.status.clone(),
+            self.reason.clone(),
+        ]
+    }
+}
+
+// clauses are separated by commas outside of parentheses and quotes
+pub fn split_clauses(sql:&str)->Vec<String> {
+    let mut clauses = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut quote: Option<char> = None;
+    for c in sql.chars() {
+        match (quote, c) {
+            (Some(q), _) if c == q => quote = None,
+            (Some(_), _) => {}
+            (None, '\'' | '"' | '`') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => depth -= 1,
+            (None, ',') if depth == 0 => {
+                clauses.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if !current.trim().is_empty() {
+        clauses.push(current.trim().to_string());
+    }
+    clauses
+}
+
+pub fn check_of(clause:&str)->Check {
+    let words = clause.split_whitespace().collect::<Vec<_>>();
+    let upper = words.iter().map(|w| w.to_uppercase()).collect::<Vec<_>>();
+    let upper = upper.iter().map(String::as_str).collect::<Vec<_>>();
+    // the name ends at the column list too, as in `ADD KEY idx(col)`
+    let ident = |i:usize| words.get(i).map(|w| w.split('(').next().unwrap_or_default().trim_matches('`').to_string());
+    let named = |i:usize, check:fn(String)->Check| match ident(i) {
+        Some(name) if !name.is_empty() => check(name),
+        _ => Check::Unknown,
+    };
+    match upper.as_slice() {
+        ["ADD", "COLUMN", ..] => named(2, Check::ColumnPresent),
+        ["ADD", "INDEX" | "KEY", ..] => named(2, Check::IndexPresent),
+        ["ADD", "UNIQUE", "INDEX" | "KEY", ..] => named(3, Check::IndexPresent),
+        ["ADD", "UNIQUE", ..] => named(2, Check::IndexPresent),
+        ["ADD", "PRIMARY" | "CONSTRAINT" | "FOREIGN" | "FULLTEXT" | "SPATIAL" | "PARTITION", ..] => Check::Unknown,
+        ["ADD", _, _, ..] => named(1, Check::ColumnPresent),
+        ["DROP", "COLUMN", ..] => named(2, Check::ColumnAbsent),
+        ["DROP", "INDEX" | "KEY", ..] => named(2, Check::IndexAbsent),
+        ["DROP", "PRIMARY" | "FOREIGN" | "CONSTRAINT" | "CHECK" | "PARTITION", ..] => Check::Unknown,
+        ["DROP", _] => named(1, Check::ColumnAbsent),
+        _ => Check::Unknown,
+    }
+}
+
+fn indexes(dbe:&DatabaseEnv, table:&str)->Vec<String> {
+    let sql = format!("SELECT DISTINCT INDEX_NAME FROM information_schema.STATISTICS \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}'");
+    let output = exe_sql_with_output(dbe, &sql);
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect()
+}
+
+// Some(true) when every clause is already in place, Some(false) when none is,
+// None when only some are, or some are while others can not be checked
+fn matches(dbe:&DatabaseEnv, table:&str, checks:&[Check])->Option<bool> {
+    let cols = columns(dbe, table);
+    let idx = indexes(dbe, table);
+    let has = |list:&[String], name:&str| list.iter().any(|n| n.eq_ignore_ascii_case(name));
+    let done = checks.iter()
+        .map(|check| match check {
+            Check::ColumnPresent(c) => Some(has(&cols, c)),
+            Check::ColumnAbsent(c) => Some(!has(&cols, c)),
+            Check::IndexPresent(i) => Some(has(&idx, i)),
+            Check::IndexAbsent(i) => Some(!has(&idx, i)),
+            Check::Unknown => None,
+        })
+        .collect::<Vec<_>>();
+    verdict(&done)
+}
+
+fn verdict(done:&[Option<bool>])->Option<bool> {
+    let unknown = done.iter().any(Option::is_none);
+    let done = done.iter().flatten().collect::<Vec<_>>();
+    match (done.iter().all(|d| **d), done.iter().any(|d| **d)) {
+        // unknown clauses are applied, the progress file keeps them from running twice
+        (_, false) => Some(false),
+        (true, true) if !unknown => Some(true),
+        _ => None,
+    }
+}
+
+// tables altered so far are listed in {basedir}/{database}-alter-{token}.progress
+pub fn progress_path(basedir:&str, database:&str, sql:&str)->String {
+    let token = batch_token(database, "ALTER", &[sql.to_string()]);
+    format!("{basedir}/{database}-alter-{token}.progress")
+}
+
+// stops at the first failure, the rest is reported pending and picked up by a resumed run
+pub fn alter(dbe:&DatabaseEnv, tables:&[String], sql:&str, progress:&str, resume:bool, dry_run:bool)->Vec<AlterReport> {
+    let checks = split_clauses(sql).iter().map(|c| check_of(c)).collect::<Vec<_>>();
+    let finished = if resume {
+        std::fs::read_to_string(progress).unwrap_or_default()
+            .lines()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    } else {
+        if !dry_run {
+            let _ = std::fs::remove_file(progress);
+        }
+        Vec::new()
+    };
+    let mut log = (!dry_run).then(|| std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(progress)
+        .expect("Failed to open alter progress file"));
+
+    let mut reports = Vec::new();
+    let mut failed = false;
+    for table in tables {
+        let report = |status:&str, reason:&str| AlterReport {
+            table: table.clone(),
+            status: status.into(),
+            reason: reason.into(),
+        };
+        if failed {
+            reports.push(report("pending", ""));
+            continue;
+        }
+        if finished.contains(table) {
+            reports.push(report("resumed", "altered by an earlier run"));
+            continue;
+        }
+        if !table_exists(dbe, table) {
+            reports.push(report("missing", ""));
+            continue;
+        }
+        match matches(dbe, table, &checks) {
+            Some(true) => reports.push(report("skipped", "already matches")),
+            None => {
+                failed = true;
+                reports.push(report("failed", "partially matches, alter it by hand"));
+            }
+            Some(false) if dry_run => reports.push(report("dry-run", "would alter")),
+            Some(false) => {
+                if exe_sql(dbe, &format!("ALTER TABLE {table} {sql}")).success() {
+                    if let Some(log) = log.as_mut() {
+                        writeln!(log, "{table}").expect("Failed to record alter progress");
+                    }
+                    reports.push(report("altered", ""));
+                } else {
+                    failed = true;
+                    reports.push(report("failed", "ALTER TABLE failed"));
+                }
+            }
+        }
+    }
+    if !failed && !dry_run {
+        let _ = std::fs::remove_file(progress);
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clauses_and_checks() {
+        let clauses = split_clauses("ADD COLUMN foo DECIMAL(10,2) NULL DEFAULT 'a,b', DROP `bar`, ADD UNIQUE KEY uk (a, b)");
+        assert_eq!(clauses.len(), 3);
+        assert_eq!(check_of(&clauses[0]), Check::ColumnPresent("foo".into()));
+        assert_eq!(check_of(&clauses[1]), Check::ColumnAbsent("bar".into()));
+        assert_eq!(check_of(&clauses[2]), Check::IndexPresent("uk".into()));
+        assert_eq!(check_of("add baz int"), Check::ColumnPresent("baz".into()));
+        assert_eq!(check_of("DROP INDEX idx_a"), Check::IndexAbsent("idx_a".into()));
+        assert_eq!(check_of("MODIFY foo BIGINT"), Check::Unknown);
+        assert_eq!(check_of("ADD PRIMARY KEY (id)"), Check::Unknown);
+        assert_eq!(check_of("ADD KEY idx(col)"), Check::IndexPresent("idx".into()));
+        assert_eq!(check_of("ADD INDEX `idx`(a, b)"), Check::IndexPresent("idx".into()));
+        assert_eq!(check_of("ADD INDEX (col)"), Check::Unknown);
+    }
+
+    #[test]
+    fn verdicts() {
+        assert_eq!(verdict(&[Some(true), Some(true)]), Some(true));
+        assert_eq!(verdict(&[Some(false), None]), Some(false));
+        assert_eq!(verdict(&[None]), Some(false));
+        assert_eq!(verdict(&[Some(true), None]), None);
+        assert_eq!(verdict(&[Some(true), Some(false)]), None);
+    }
+}
@@ -0,0 +1,265 @@
+use std::process::Command;
+
+use super::{count_rows, dump_out_with, exe_sql_with_output};
+use super::dump::DumpOptions;
+use super::clock;
+use super::panelenv::DatabaseEnv;
+use super::schema::{columns, quote_ident};
+use super::session::Session;
+
+// [backup]
+// mode = "refuse"   # "off" (default), "refuse" a destroy without a verified dump, or "dump" it first
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct BackupEnv {
+    #[serde(default)]
+    pub mode: BackupMode,
+}
+
+#[derive(Debug, Default, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackupMode {
+    #[default]
+    Off,
+    Refuse,
+    Dump,
+}
+
+// how the checksum of a dump was read, verify reads the live table the same way
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChecksumKind {
+    // CHECKSUM TABLE, read while the table was locked
+    #[default]
+    Table,
+    // the summed row hashes of state_sql, read in the snapshot of the dump
+    Rows,
+}
+
+// written next to every {table}.sql as {table}.sql.meta
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct DumpMeta {
+    pub table: String,
+    pub rows: u64,
+    pub checksum: String,
+    #[serde(default)]
+    pub checksum_kind: ChecksumKind,
+    pub file_sha256: String,
+    pub file_size: u64,
+    pub dumped_at: String,
+}
+
+pub fn meta_path(outdir:&str, table:&str)->String {
+    format!("{outdir}/{table}.sql.meta")
+}
+
+pub fn read_meta(outdir:&str, table:&str)->Option<DumpMeta> {
+    let content = std::fs::read_to_string(meta_path(outdir, table)).ok()?;
+    toml::from_str(&content).ok()
+}
+
+// locks the table READ for the rest of the session and reads its rows and checksum,
+// nothing can change them until the session closes
+pub fn locked_state(session:&mut Session, table:&str)->Result<(u64, String), String> {
+    if session.execute(&format!("LOCK TABLES {table} READ"), ";")?.failed {
+        return Err(format!("failed to lock {table}"));
+    }
+    let rows = session.row(&format!("SELECT COUNT(*) FROM {table}"))?
+        .first()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| format!("failed to count {table}"))?;
+    let checksum = session.row(&format!("CHECKSUM TABLE {table}"))?
+        .last()
+        .and_then(|c| parse_checksum(c))
+        .ok_or_else(|| format!("failed to checksum {table}"))?;
+    Ok((rows, checksum))
+}
+
+// the row count and the summed row hashes in one statement, so both come from the same read;
+// run inside a snapshot they describe exactly the rows of that snapshot
+pub fn state_sql(dbe:&DatabaseEnv, table:&str)->String {
+    let quoted = columns(dbe, table).iter()
+        .map(|c| format!("QUOTE({})", quote_ident(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("SELECT COUNT(*), COALESCE(SUM(CAST(CONV(LEFT(MD5(CONCAT_WS(0x1f, {quoted})), 16), 16, 10) AS UNSIGNED)), 0) FROM {table}")
+}
+
+fn parse_state(table:&str, row:&[String])->Result<(u64, String), String> {
+    match row {
+        [rows, checksum] => rows.parse()
+            .map(|rows| (rows, checksum.clone()))
+            .map_err(|_| format!("failed to count {table}")),
+        _ => Err(format!("failed to checksum {table}")),
+    }
+}
+
+// rows and checksum of the table in the snapshot or lock the session holds
+pub fn session_state(session:&mut Session, dbe:&DatabaseEnv, table:&str)->Result<(u64, String), String> {
+    parse_state(table, &session.row(&state_sql(dbe, table))?)
+}
+
+// rows and checksum of the table as of now
+pub fn rows_state(dbe:&DatabaseEnv, table:&str)->Result<(u64, String), String> {
+    let output = exe_sql_with_output(dbe, &state_sql(dbe, table));
+    if !output.status.success() {
+        return Err(format!("failed to checksum {table}"));
+    }
+    let row = String::from_utf8_lossy(&output.stdout)
+        .trim_end()
+        .split('\t')
+        .map(String::from)
+        .collect::<Vec<_>>();
+    parse_state(table, &row)
+}
+
+// record what the dump {outdir}/{table}.sql holds, rows and checksum read in the same lock or
+// snapshot as the dump, call right after a successful dump
+pub fn write_meta(table:&str, outdir:&str, rows:u64, checksum:&str, checksum_kind:ChecksumKind)->Option<DumpMeta> {
+    let sqlfile = format!("{outdir}/{table}.sql");
+    let meta = DumpMeta {
+        table: table.to_string(),
+        rows,
+        checksum: checksum.to_string(),
+        checksum_kind,
+        file_sha256: file_sha256(&sqlfile)?,
+        file_size: std::fs::metadata(&sqlfile).ok()?.len(),
+        dumped_at: clock::now_stamp(),
+    };
+    let content = toml::to_string(&meta).expect("serialize dump meta");
+    std::fs::write(meta_path(outdir, table), content).ok()?;
+    Some(meta)
+}
+
+// the dump is verified when its file is intact and the live table still holds the same data
+pub fn verify(dbe:&DatabaseEnv, table:&str, outdir:&str)->Result<DumpMeta, String> {
+    let Some(meta) = read_meta(outdir, table) else {
+        return Err(format!("no dump of {table} under {outdir}"));
+    };
+    let (rows, checksum) = match meta.checksum_kind {
+        ChecksumKind::Table => (count_rows(dbe, table), table_checksum(dbe, table)),
+        ChecksumKind::Rows => rows_state(dbe, table).map_or((None, None), |(r, c)| (Some(r), Some(c))),
+    };
+    compare(&meta, outdir, rows, checksum.as_deref())?;
+    Ok(meta)
+}
+
+// the file against its recorded hash, then the live rows and checksum against the recorded ones
+fn compare(meta:&DumpMeta, outdir:&str, rows:Option<u64>, checksum:Option<&str>)->Result<(), String> {
+    let table = &meta.table;
+    let sqlfile = format!("{outdir}/{table}.sql");
+    if file_sha256(&sqlfile).as_deref() != Some(meta.file_sha256.as_str()) {
+        return Err(format!("{sqlfile} is missing or changed since it was dumped"));
+    }
+    if rows != Some(meta.rows) {
+        return Err(format!("{table} row count differs from its dump at {}", meta.dumped_at));
+    }
+    if checksum != Some(meta.checksum.as_str()) {
+        return Err(format!("{table} checksum differs from its dump at {}", meta.dumped_at));
+    }
+    Ok(())
+}
+
+// true when the table may be destroyed, a missing dump is written with the [dump] options of its name
+pub fn ensure(dbe:&DatabaseEnv, backup:&BackupEnv, table:&str, outdir:&str, options:&DumpOptions)->bool {
+    if backup.mode == BackupMode::Off {
+        return true;
+    }
+    let reason = match verify(dbe, table, outdir) {
+        Ok(_) => return true,
+        Err(reason) => reason,
+    };
+    eprintln!("----- {reason} -----");
+    if backup.mode == BackupMode::Refuse {
+        return false;
+    }
+    // a backup is always the whole table
+    let options = DumpOptions {
+        filter: None,
+        mode: None,
+        ..options.clone()
+    };
+    if !dump_out_with(dbe, table, outdir, &options).success() {
+        return false;
+    }
+    match verify(dbe, table, outdir) {
+        Ok(_) => true,
+        Err(reason) => {
+            eprintln!("----- {reason} -----");
+            false
+        }
+    }
+}
+
+// the {basedir}/{year} holding a dump of the table, if any
+pub fn locate(basedir:&str, table:&str)->Option<String> {
+    let mut dirs = std::fs::read_dir(basedir).ok()?
+        .filter_map(|e| e.ok())
+        .filter(|e| e.path().is_dir())
+        .filter_map(|e| e.path().to_str().map(String::from))
+        .collect::<Vec<_>>();
+    dirs.sort();
+    dirs.into_iter().rev().find(|dir| std::path::Path::new(&meta_path(dir, table)).exists())
+}
+
+pub fn table_checksum(dbe:&DatabaseEnv, table:&str)->Option<String> {
+    let output = exe_sql_with_output(dbe, &format!("CHECKSUM TABLE {table}"));
+    if !output.status.success() {
+        return None;
+    }
+    parse_checksum(&String::from_utf8_lossy(&output.stdout))
+}
+
+// the checksum field of a CHECKSUM TABLE row, None for a missing table
+pub fn parse_checksum(row:&str)->Option<String> {
+    let checksum = row.trim_end().rsplit('\t').next()?;
+    if checksum == "NULL" {
+        return None;
+    }
+    Some(checksum.to_string())
+}
+
+pub fn file_sha256(path:&str)->Option<String> {
+    let output = Command::new("sha256sum")
+        .arg(path)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.split_whitespace().next().map(String::from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn meta_round_trip() {
+        let outdir = std::env::temp_dir().join(format!("migrate-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&outdir).unwrap();
+        let outdir = outdir.to_str().unwrap();
+        let sqlfile = format!("{outdir}/sales_1701.sql");
+        std::fs::write(&sqlfile, "INSERT INTO `sales_1701` VALUES (1);\n").unwrap();
+
+        let written = write_meta("sales_1701", outdir, 1, "9a3f", ChecksumKind::Rows).unwrap();
+        let meta = read_meta(outdir, "sales_1701").unwrap();
+        assert_eq!(meta, written);
+        assert_eq!(meta.checksum_kind, ChecksumKind::Rows);
+        assert!(compare(&meta, outdir, Some(1), Some("9a3f")).is_ok());
+        assert!(compare(&meta, outdir, Some(2), Some("9a3f")).is_err());
+        assert!(compare(&meta, outdir, Some(1), Some("9a40")).is_err());
+        assert!(compare(&meta, outdir, None, None).is_err());
+
+        std::fs::write(&sqlfile, "INSERT INTO `sales_1701` VALUES (2);\n").unwrap();
+        assert!(compare(&meta, outdir, Some(1), Some("9a3f")).is_err());
+        std::fs::remove_dir_all(outdir).unwrap();
+    }
+
+    #[test]
+    fn meta_without_kind_is_table_checksum() {
+        let meta: DumpMeta = toml::from_str("table = \"t\"\nrows = 3\nchecksum = \"42\"\n\
+            file_sha256 = \"ab\"\nfile_size = 10\ndumped_at = \"20170101000000\"\n").unwrap();
+        assert_eq!(meta.checksum_kind, ChecksumKind::Table);
+    }
+}
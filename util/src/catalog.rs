@@ -0,0 +1,158 @@
+use std::process::Command;
+
+use super::backup::{file_sha256, read_meta};
+use super::clock;
+use super::confirm::wildcard;
+use super::report::{opt, Row};
+
+// {basedir}/catalog.json lists every dump dumpout wrote and the zip holding it
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct CatalogEntry {
+    pub table: String,
+    pub name: String,
+    // yymm
+    pub period: String,
+    pub file: String,
+    pub archive: Option<String>,
+    pub member: Option<String>,
+    pub size: Option<u64>,
+    pub sha256: Option<String>,
+    pub rows: Option<u64>,
+    pub recorded_at: String,
+}
+
+impl Row for CatalogEntry {
+    const COLUMNS: &'static [&'static str] = &["table", "period", "file", "archive", "member", "size", "rows", "recorded_at"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.table.clone(),
+            self.period.clone(),
+            self.file.clone(),
+            opt(self.archive.as_ref()),
+            opt(self.member.as_ref()),
+            opt(self.size),
+            opt(self.rows),
+            self.recorded_at.clone(),
+        ]
+    }
+}
+
+pub fn catalog_path(basedir:&str)->String {
+    format!("{basedir}/catalog.json")
+}
+
+pub fn load(basedir:&str)->Vec<CatalogEntry> {
+    std::fs::read_to_string(catalog_path(basedir)).ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+fn save(basedir:&str, entries:&[CatalogEntry])->std::io::Result<()> {
+    let content = serde_json::to_string_pretty(entries).expect("serialize catalog");
+    std::fs::write(catalog_path(basedir), content)
+}
+
+// an entry for the same file is replaced
+fn upsert(entries:&mut Vec<CatalogEntry>, entry:CatalogEntry) {
+    match entries.iter_mut().find(|e| e.file == entry.file) {
+        Some(e) => *e = entry,
+        None => entries.push(entry),
+    }
+}
+
+// after dumpout wrote {basedir}/{year}/{table}.sql and its meta
+pub fn record_dump(basedir:&str, name:&str, year:&str, month:&str, table:&str)->std::io::Result<()> {
+    let outdir = format!("{basedir}/{year}");
+    let file = format!("{outdir}/{table}.sql");
+    let meta = read_meta(&outdir, table);
+    let entry = CatalogEntry {
+        table: table.to_string(),
+        name: name.to_string(),
+        period: format!("{year}{month}"),
+        file: file.clone(),
+        archive: None,
+        member: None,
+        size: std::fs::metadata(&file).ok().map(|m| m.len()),
+        sha256: meta.as_ref().map(|m| m.file_sha256.clone()).or_else(|| file_sha256(&file)),
+        rows: meta.map(|m| m.rows),
+        recorded_at: clock::now_stamp(),
+    };
+    let mut entries = load(basedir);
+    upsert(&mut entries, entry);
+    save(basedir, &entries)
+}
+
+// after zip packed {basedir}/{year}/{name}{year}.zip, its members are marked as archived there
+pub fn record_zip(basedir:&str, year:&str, name:&str)->std::io::Result<()> {
+    let zipfile = format!("{basedir}/{year}/{name}{year}.zip");
+    let output = Command::new("unzip").arg("-Z1").arg(&zipfile).output()?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!("unzip -Z1 {zipfile} finished with: {}", output.status)));
+    }
+    let mut entries = load(basedir);
+    for member in String::from_utf8_lossy(&output.stdout).lines() {
+        let Some(table) = member.rsplit('/').next().and_then(|f| f.strip_suffix(".sql")) else {
+            continue;
+        };
+        let file = format!("{basedir}/{year}/{table}.sql");
+        let mut entry = entries.iter().find(|e| e.file == file).cloned().unwrap_or_else(|| CatalogEntry {
+            table: table.to_string(),
+            name: name.to_string(),
+            period: table.strip_prefix(name)
+                .map(|rest| rest.chars().take(4).collect())
+                .unwrap_or_default(),
+            file: file.clone(),
+            archive: None,
+            member: None,
+            size: None,
+            sha256: None,
+            rows: None,
+            recorded_at: clock::now_stamp(),
+        });
+        entry.archive = Some(zipfile.clone());
+        entry.member = Some(member.to_string());
+        upsert(&mut entries, entry);
+    }
+    save(basedir, &entries)
+}
+
+pub fn search(basedir:&str, pattern:&str)->Vec<CatalogEntry> {
+    load(basedir).into_iter()
+        .filter(|e| wildcard(pattern, &e.table))
+        .collect()
+}
+
+// the newest entry of the table
+pub fn find(basedir:&str, table:&str)->Option<CatalogEntry> {
+    load(basedir).into_iter()
+        .filter(|e| e.table == table)
+        .max_by(|a, b| a.recorded_at.cmp(&b.recorded_at))
+}
+
+// the dump file of the entry, extracted from its archive into {basedir}/restore when the file is gone,
+// and checked against the recorded sha256
+pub fn fetch(basedir:&str, entry:&CatalogEntry)->Result<String, String> {
+    let path = if std::path::Path::new(&entry.file).exists() {
+        entry.file.clone()
+    } else {
+        let (Some(archive), Some(member)) = (&entry.archive, &entry.member) else {
+            return Err(format!("{} is gone and not in any archive", entry.file));
+        };
+        let restoredir = format!("{basedir}/restore");
+        std::fs::create_dir_all(&restoredir).map_err(|e| format!("{restoredir}: {e}"))?;
+        let path = format!("{restoredir}/{}.sql", entry.table);
+        let file = std::fs::File::create(&path).map_err(|e| format!("{path}: {e}"))?;
+        let status = Command::new("unzip").arg("-p").arg(archive).arg(member)
+            .stdout(file)
+            .status()
+            .map_err(|e| format!("unzip: {e}"))?;
+        if !status.success() {
+            return Err(format!("unzip -p {archive} {member} finished with: {status}"));
+        }
+        path
+    };
+    match &entry.sha256 {
+        Some(sha) if file_sha256(&path).as_ref() != Some(sha) => Err(format!("{path} does not match the catalog sha256")),
+        _ => Ok(path),
+    }
+}
@@ -1,5 +1,6 @@
-use std::{env::{args, current_dir, current_exe}, path::{Path, PathBuf}};
+use std::{env::{current_dir, current_exe}, path::{Path, PathBuf}};
 
+#[allow(dead_code)]
 fn get_exe_dir()->String {
     std::env::current_exe()
         .unwrap().parent()
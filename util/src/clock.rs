@@ -0,0 +1,109 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// stamps are UTC and look like 20261019083000 (YYYYMMDDhhmmss),
+// they are short enough to be put into a table name
+pub const STAMP_LEN: usize = 14;
+
+pub fn now_secs()->u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before 1970")
+        .as_secs()
+}
+
+pub fn now_stamp()->String {
+    to_stamp(now_secs())
+}
+
+pub fn to_stamp(secs:u64)->String {
+    let days = (secs / 86400) as i64;
+    let rest = secs % 86400;
+    let (y, m, d) = civil_from_days(days);
+    format!("{y:04}{m:02}{d:02}{:02}{:02}{:02}", rest / 3600, rest % 3600 / 60, rest % 60)
+}
+
+// the UTC (year, month) of secs
+pub fn year_month(secs:u64)->(i64, i64) {
+    let (y, m, _) = civil_from_days((secs / 86400) as i64);
+    (y, m)
+}
+
+pub fn from_stamp(stamp:&str)->Option<u64> {
+    if stamp.len() != STAMP_LEN || !stamp.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let num = |r:std::ops::Range<usize>| stamp[r].parse::<i64>().unwrap();
+    let (y, m, d) = (num(0..4), num(4..6), num(6..8));
+    let (hh, mm, ss) = (num(8..10), num(10..12), num(12..14));
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) || hh > 23 || mm > 59 || ss > 59 {
+        return None;
+    }
+    let days = days_from_civil(y, m, d);
+    u64::try_from(days * 86400 + hh * 3600 + mm * 60 + ss).ok()
+}
+
+// 30d, 12h, 45m, 90s or a plain number of seconds
+pub fn parse_duration(s:&str)->Option<u64> {
+    let s = s.trim();
+    let (num, unit) = match s.find(|c:char| !c.is_ascii_digit()) {
+        Some(pos) => s.split_at(pos),
+        None => (s, "s"),
+    };
+    let num = num.parse::<u64>().ok()?;
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        "w" => 7 * 86400,
+        _ => return None,
+    };
+    num.checked_mul(unit)
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn civil_from_days(z:i64)->(i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let y = yoe + era * 400;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if m <= 2 { y + 1 } else { y }, m, d)
+}
+
+fn days_from_civil(y:i64, m:i64, d:i64)->i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y.rem_euclid(400);
+    let mp = if m > 2 { m - 3 } else { m + 9 };
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stamp_round_trip() {
+        assert_eq!(to_stamp(0), "19700101000000");
+        assert_eq!(to_stamp(1_800_000_000), "20270115080000");
+        assert_eq!(from_stamp("20270115080000"), Some(1_800_000_000));
+        assert_eq!(from_stamp("2027011508000x"), None);
+        assert_eq!(from_stamp("20271315080000"), None);
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("30d"), Some(30 * 86400));
+        assert_eq!(parse_duration("12h"), Some(12 * 3600));
+        assert_eq!(parse_duration("90"), Some(90));
+        assert_eq!(parse_duration("3y"), None);
+        assert_eq!(parse_duration("d"), None);
+    }
+}
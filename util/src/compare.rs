@@ -0,0 +1,83 @@
+use super::{count_rows, table_exists};
+use super::backup::table_checksum;
+use super::panelenv::{DatabaseEnv, TableRule};
+use super::report::{opt, Row};
+
+// one rule table on both sides, the sides may be postfixed copies or other databases
+#[derive(Debug, serde::Serialize)]
+pub struct CompareRow {
+    pub left_table: String,
+    pub right_table: String,
+    pub left_rows: Option<u64>,
+    pub right_rows: Option<u64>,
+    pub left_checksum: Option<String>,
+    pub right_checksum: Option<String>,
+    pub status: String,
+}
+
+impl Row for CompareRow {
+    const COLUMNS: &'static [&'static str] = &["left_table", "right_table", "left_rows", "right_rows",
+        "left_checksum", "right_checksum", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.left_table.clone(),
+            self.right_table.clone(),
+            opt(self.left_rows),
+            opt(self.right_rows),
+            opt(self.left_checksum.as_ref()),
+            opt(self.right_checksum.as_ref()),
+            self.status.clone(),
+        ]
+    }
+}
+
+impl CompareRow {
+    pub fn same(&self)->bool {
+        self.status == "same"
+    }
+}
+
+struct Side {
+    rows: Option<u64>,
+    checksum: Option<String>,
+}
+
+fn probe(dbe:&DatabaseEnv, table:&str)->Option<Side> {
+    if !table_exists(dbe, table) {
+        return None;
+    }
+    Some(Side {
+        rows: count_rows(dbe, table),
+        checksum: table_checksum(dbe, table),
+    })
+}
+
+pub fn compare(left:&DatabaseEnv, left_postfix:&str, right:&DatabaseEnv, right_postfix:&str, rule:&TableRule)->Vec<CompareRow> {
+    rule.tables().into_iter()
+        .map(|table| {
+            let left_table = format!("{table}{left_postfix}");
+            let right_table = format!("{table}{right_postfix}");
+            let l = probe(left, &left_table);
+            let r = probe(right, &right_table);
+            let status = match (&l, &r) {
+                (None, None) => "missing both",
+                (None, Some(_)) => "missing left",
+                (Some(_), None) => "missing right",
+                (Some(l), Some(r)) if l.rows != r.rows => "rows differ",
+                (Some(l), Some(r)) if l.checksum != r.checksum => "checksum differs",
+                _ => "same",
+            };
+            let (left_rows, left_checksum) = l.map_or((None, None), |s| (s.rows, s.checksum));
+            let (right_rows, right_checksum) = r.map_or((None, None), |s| (s.rows, s.checksum));
+            CompareRow {
+                left_table,
+                right_table,
+                left_rows,
+                right_rows,
+                left_checksum,
+                right_checksum,
+                status: status.into(),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,154 @@
+use std::io::Write;
+
+// [confirm]
+// mode = "database"            # type the database name, or "count": type the number of tables
+// protected = ["user*", "dict_?"]
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ConfirmEnv {
+    #[serde(default)]
+    pub mode: ConfirmMode,
+    #[serde(default)]
+    pub protected: Vec<String>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConfirmMode {
+    #[default]
+    Database,
+    Count,
+}
+
+// --yes is honoured only together with the --confirm-token of the very same batch
+#[derive(Debug, Default)]
+pub struct Approval<'a> {
+    pub yes: bool,
+    pub token: Option<&'a str>,
+}
+
+impl ConfirmEnv {
+    pub fn is_protected(&self, table:&str)->bool {
+        self.protected.iter().any(|pattern| wildcard(pattern, table))
+    }
+
+    pub fn protected_of<'t>(&self, tables:&'t [String])->Vec<&'t str> {
+        tables.iter()
+            .map(String::as_str)
+            .filter(|t| self.is_protected(t))
+            .collect()
+    }
+
+    // print every table of the batch, then ask once for all of them
+    pub fn confirm_batch(&self, database:&str, action:&str, tables:&[String], approval:&Approval)->bool {
+        let protected = self.protected_of(tables);
+        if !protected.is_empty() {
+            eprintln!("----- protected tables can not be {action}: {} -----", protected.join(" "));
+            return false;
+        }
+        if tables.is_empty() {
+            return true;
+        }
+        for table in tables {
+            println!("  \x1b[31m{action}\x1b[0m {database}.{table}");
+        }
+        let token = batch_token(database, action, tables);
+        if approval.yes {
+            return match approval.token {
+                Some(t) if t == token => true,
+                Some(_) => {
+                    eprintln!("----- --confirm-token does not match this batch -----");
+                    false
+                }
+                None => {
+                    eprintln!("----- --yes requires --confirm-token -----");
+                    false
+                }
+            };
+        }
+        let expect = match self.mode {
+            ConfirmMode::Database => database.to_string(),
+            ConfirmMode::Count => tables.len().to_string(),
+        };
+        let what = match self.mode {
+            ConfirmMode::Database => "the database name",
+            ConfirmMode::Count => "the number of tables",
+        };
+        println!("to run this batch unattended: --yes --confirm-token {token}");
+        print!("You will \x1b[31m{action}\x1b[0m the {} tables above, input {what} to confirm: ", tables.len());
+        std::io::stdout().flush().unwrap();
+        let mut input = String::new();
+        std::io::stdin()
+            .read_line(&mut input)
+            .expect("read input failly");
+        if input.trim() != expect {
+            eprintln!("----- confirmation does not match, {action} canceled -----");
+            return false;
+        }
+        true
+    }
+}
+
+// a short fingerprint of what will be done, so an approval can not be replayed on another batch
+pub fn batch_token(database:&str, action:&str, tables:&[String])->String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    let mut feed = |bytes:&[u8]| {
+        for b in bytes.iter().chain(b"\n") {
+            hash ^= *b as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+    feed(database.as_bytes());
+    feed(action.as_bytes());
+    for table in tables {
+        feed(table.as_bytes());
+    }
+    format!("{hash:016x}")
+}
+
+// * matches any run of chars, ? matches exactly one
+pub fn wildcard(pattern:&str, text:&str)->bool {
+    let p = pattern.as_bytes();
+    let t = text.as_bytes();
+    let (mut pi, mut ti) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while ti < t.len() {
+        if pi < p.len() && (p[pi] == b'?' || p[pi] == t[ti]) {
+            pi += 1;
+            ti += 1;
+        } else if pi < p.len() && p[pi] == b'*' {
+            star = Some((pi, ti));
+            pi += 1;
+        } else if let Some((sp, st)) = star {
+            pi = sp + 1;
+            ti = st + 1;
+            star = Some((sp, st + 1));
+        } else {
+            return false;
+        }
+    }
+    p[pi..].iter().all(|b| *b == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wildcard_match() {
+        assert!(wildcard("user*", "user1701"));
+        assert!(wildcard("*_keep", "sales1701_keep"));
+        assert!(wildcard("dict_?", "dict_a"));
+        assert!(!wildcard("dict_?", "dict_ab"));
+        assert!(!wildcard("user*", "sales1701"));
+        assert!(wildcard("*", ""));
+    }
+
+    #[test]
+    fn token_depends_on_batch() {
+        let a = vec!["t1701".to_string(), "t1702".to_string()];
+        let b = vec!["t1701".to_string()];
+        assert_eq!(batch_token("db", "DROP", &a), batch_token("db", "DROP", &a));
+        assert_ne!(batch_token("db", "DROP", &a), batch_token("db", "DROP", &b));
+        assert_ne!(batch_token("db", "DROP", &a), batch_token("db2", "DROP", &a));
+    }
+}
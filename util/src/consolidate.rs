@@ -0,0 +1,169 @@
+use super::{add_postfix, count_rows, create_empty, drop_sql, exe_sql, exe_sql_with_output, table_exists};
+use super::panelenv::{DatabaseEnv, TableRule};
+use super::report::{opt, Row};
+use super::schema::{auto_increment_column, primary_key, quote_ident, unique_keys_without};
+
+#[derive(Debug, serde::Serialize)]
+pub struct ConsolidateReport {
+    pub target: String,
+    pub table: String,
+    pub rows: Option<u64>,
+    pub copied: Option<u64>,
+    pub status: String,
+}
+
+impl Row for ConsolidateReport {
+    const COLUMNS: &'static [&'static str] = &["target", "table", "rows", "copied", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.target.clone(),
+            self.table.clone(),
+            opt(self.rows),
+            opt(self.copied),
+            self.status.clone(),
+        ]
+    }
+}
+
+// PARTITION BY RANGE over the days of column, one partition per month of the rule
+pub fn partition_sql(target:&str, column:&str, year:&str, months:&[&str])->String {
+    let century = 2000 + year.parse::<i64>().unwrap_or(0);
+    let partitions = months.iter()
+        .map(|month| {
+            let m = month.parse::<i64>().unwrap_or(0);
+            let (y, m) = if m == 12 { (century + 1, 1) } else { (century, m + 1) };
+            format!("PARTITION p{year}{month} VALUES LESS THAN (TO_DAYS('{y:04}-{m:02}-01'))")
+        })
+        .chain(std::iter::once("PARTITION pmax VALUES LESS THAN MAXVALUE".to_string()))
+        .collect::<Vec<_>>()
+        .join(", ");
+    format!("ALTER TABLE {target} PARTITION BY RANGE (TO_DAYS({})) ({partitions})", quote_ident(column))
+}
+
+// the first two months whose AUTO_INCREMENT primary key ranges overlap, their rows would collide
+// in the target; a key of more columns may repeat the id and is not checked
+fn overlapping_ids(dbe:&DatabaseEnv, tables:&[String])->Option<(String, String)> {
+    let first = tables.first()?;
+    let id = auto_increment_column(dbe, first)?;
+    if primary_key(dbe, first) != [id.clone()] {
+        return None;
+    }
+    let id = quote_ident(&id);
+    let mut ranges = tables.iter()
+        .filter_map(|table| {
+            let output = exe_sql_with_output(dbe, &format!("SELECT MIN({id}), MAX({id}) FROM {table}"));
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let (min, max) = stdout.trim_end().split_once('\t')?;
+            // an empty month has NULL bounds
+            Some((min.parse::<i128>().ok()?, max.parse::<i128>().ok()?, table))
+        })
+        .collect::<Vec<_>>();
+    ranges.sort();
+    ranges.windows(2)
+        .find(|w| w[1].0 <= w[0].1)
+        .map(|w| (w[0].2.clone(), w[1].2.clone()))
+}
+
+// {name}{year} gets every month of the rule, each copy is verified by the rows the target gained,
+// the monthly tables get the postfix only when every month of their name was copied,
+// otherwise the target is dropped
+pub fn consolidate(dbe:&DatabaseEnv, rule:&TableRule, year:&str, partition:Option<&str>, postfix:Option<&str>)->Vec<ConsolidateReport> {
+    let mut reports = Vec::new();
+    for name in &rule.names {
+        let target = format!("{name}{year}");
+        let tables = rule.months.iter()
+            .map(|month| format!("{name}{year}{month}"))
+            .filter(|table| table_exists(dbe, table))
+            .collect::<Vec<_>>();
+        let report = |table:&str, rows, copied, status:&str| ConsolidateReport {
+            target: target.clone(),
+            table: table.into(),
+            rows,
+            copied,
+            status: status.into(),
+        };
+        let Some(first) = tables.first() else {
+            reports.push(report("", None, None, "no monthly tables"));
+            continue;
+        };
+        if table_exists(dbe, &target) {
+            reports.push(report("", None, None, "target exists"));
+            continue;
+        }
+        // both would only fail once the target exists and half of the months are copied
+        if let Some(column) = partition {
+            let keys = unique_keys_without(dbe, first, column);
+            if !keys.is_empty() {
+                reports.push(report(first, None, None, &format!("unique key {} lacks partition column {column}", keys.join(", "))));
+                continue;
+            }
+        }
+        if let Some((earlier, later)) = overlapping_ids(dbe, &tables) {
+            reports.push(report(&later, None, None, &format!("ids overlap {earlier}")));
+            continue;
+        }
+        if !create_empty(dbe, first, &target).success() {
+            reports.push(report("", None, None, "create failed"));
+            continue;
+        }
+        let partitioned = partition.is_none_or(|column| {
+            exe_sql(dbe, &partition_sql(&target, column, year, &rule.months)).success()
+        });
+        let mut complete = partitioned;
+        if !partitioned {
+            reports.push(report("", None, None, "partitioning failed"));
+        }
+
+        let mut total = 0;
+        for table in tables.iter().filter(|_| partitioned) {
+            let rows = count_rows(dbe, table);
+            let copied = exe_sql(dbe, &format!("INSERT INTO {target} SELECT * FROM {table}"))
+                .success()
+                .then(|| count_rows(dbe, &target))
+                .flatten()
+                .map(|after| after.saturating_sub(total));
+            let status = match (rows, copied) {
+                (Some(rows), Some(copied)) if rows == copied => "copied",
+                (_, None) => "copy failed",
+                _ => "count mismatch",
+            };
+            total += copied.unwrap_or(0);
+            reports.push(report(table, rows, copied, status));
+            if status != "copied" {
+                complete = false;
+                break;
+            }
+        }
+        // a target which did not get every month is dropped again, so the next run starts over
+        if !complete {
+            if !exe_sql(dbe, &drop_sql(&target)).success() {
+                reports.push(report("", None, None, "drop of the incomplete target failed"));
+            }
+            continue;
+        }
+        let Some(postfix) = postfix else {
+            continue;
+        };
+        for table in &tables {
+            if !add_postfix(dbe, table, postfix).success() {
+                reports.push(report(table, None, None, "rename failed"));
+                break;
+            }
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn monthly_partitions() {
+        let sql = partition_sql("sales17", "day", "17", &["11", "12"]);
+        assert_eq!(sql, "ALTER TABLE sales17 PARTITION BY RANGE (TO_DAYS(`day`)) (\
+            PARTITION p1711 VALUES LESS THAN (TO_DAYS('2017-12-01')), \
+            PARTITION p1712 VALUES LESS THAN (TO_DAYS('2018-01-01')), \
+            PARTITION pmax VALUES LESS THAN MAXVALUE)");
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+// [dump]
+// single_transaction = true
+// builtin = true   # no mysqldump needed
+// [dump.names.sales]
+// where = "day >= '2017-01-15'"
+// mode = "data"   # "full" (default), "schema" or "data"
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct DumpEnv {
+    #[serde(flatten)]
+    pub defaults: DumpOptions,
+    #[serde(default)]
+    pub names: BTreeMap<String, DumpOptions>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DumpMode {
+    #[default]
+    Full,
+    Schema,
+    Data,
+}
+
+// every option left None falls back to the [dump] defaults, then to plain mysqldump
+#[derive(Debug, Default, Clone, serde::Deserialize)]
+pub struct DumpOptions {
+    #[serde(rename = "where")]
+    pub filter: Option<String>,
+    pub mode: Option<DumpMode>,
+    pub single_transaction: Option<bool>,
+    pub quick: Option<bool>,
+    pub skip_lock_tables: Option<bool>,
+    // bytes per multi-row INSERT, 0 writes one INSERT per row
+    pub extended_insert: Option<u64>,
+    // written by sqldump instead of mysqldump
+    pub builtin: Option<bool>,
+}
+
+impl DumpOptions {
+    // self wins over base
+    pub fn or(&self, base:&DumpOptions)->DumpOptions {
+        DumpOptions {
+            filter: self.filter.clone().or_else(|| base.filter.clone()),
+            mode: self.mode.or(base.mode),
+            single_transaction: self.single_transaction.or(base.single_transaction),
+            quick: self.quick.or(base.quick),
+            skip_lock_tables: self.skip_lock_tables.or(base.skip_lock_tables),
+            extended_insert: self.extended_insert.or(base.extended_insert),
+            builtin: self.builtin.or(base.builtin),
+        }
+    }
+
+    // a dump of less than the whole table is not a backup
+    pub fn is_complete(&self)->bool {
+        self.filter.is_none() && self.mode.unwrap_or_default() == DumpMode::Full
+    }
+
+    // mysqldump locks the tables it dumps unless told to take a snapshot or not to lock
+    pub fn locks(&self)->bool {
+        !self.single_transaction.unwrap_or(false) && !self.skip_lock_tables.unwrap_or(false)
+    }
+
+    // the mysqldump arguments, the where clause quoted for sh
+    pub fn args(&self)->Vec<String> {
+        let mut args = Vec::new();
+        match self.mode.unwrap_or_default() {
+            DumpMode::Full => {}
+            DumpMode::Schema => args.push("--no-data".into()),
+            DumpMode::Data => args.push("--no-create-info".into()),
+        }
+        let switches = [
+            (self.single_transaction, "--single-transaction"),
+            (self.quick, "--quick"),
+            (self.skip_lock_tables, "--skip-lock-tables"),
+        ];
+        args.extend(switches.iter()
+            .filter(|(on, _)| on.unwrap_or(false))
+            .map(|(_, arg)| arg.to_string()));
+        match self.extended_insert {
+            Some(0) => args.push("--skip-extended-insert".into()),
+            Some(size) => args.push(format!("--net-buffer-length={size}")),
+            None => {}
+        }
+        if let Some(filter) = &self.filter {
+            args.push(format!("--where='{}'", filter.replace('\'', r"'\''")));
+        }
+        args
+    }
+}
+
+impl DumpEnv {
+    pub fn options_for(&self, name:&str)->DumpOptions {
+        self.names.get(name)
+            .map_or_else(|| self.defaults.clone(), |options| options.or(&self.defaults))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn per_name_options() {
+        let env: DumpEnv = toml::from_str("single_transaction = true\n\
+            extended_insert = 0\n\
+            [names.sales]\n\
+            where = \"day >= '2017-01-15'\"\n\
+            mode = \"data\"\n").unwrap();
+        let sales = env.options_for("sales");
+        assert!(!sales.is_complete());
+        assert_eq!(sales.args(), vec![
+            "--no-create-info",
+            "--single-transaction",
+            "--skip-extended-insert",
+            r"--where='day >= '\''2017-01-15'\'''",
+        ]);
+        assert!(env.options_for("users").is_complete());
+    }
+}
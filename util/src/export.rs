@@ -0,0 +1,346 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::process::{ChildStdin, Command, Stdio};
+
+use super::{batch_field, exe_sql_spawn};
+use super::backup::file_sha256;
+use super::clock;
+use super::panelenv::DatabaseEnv;
+use super::report::{csv_field, Row};
+use super::schema::{column_info, quote_ident, ColumnInfo};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ExportFormat {
+    Csv,
+    Parquet,
+    Jsonl,
+}
+
+impl ExportFormat {
+    pub fn parse(s:&str)->Option<Self> {
+        match s {
+            "csv" => Some(Self::Csv),
+            "parquet" => Some(Self::Parquet),
+            "jsonl" => Some(Self::Jsonl),
+            _ => None,
+        }
+    }
+
+    pub fn ext(&self)->&'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+            Self::Jsonl => "jsonl",
+        }
+    }
+}
+
+// csv and jsonl files are compressed whole, parquet uses it as its page codec
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    pub fn parse(s:&str)->Option<Self> {
+        match s {
+            "none" => Some(Self::None),
+            "gzip" => Some(Self::Gzip),
+            "zstd" => Some(Self::Zstd),
+            _ => None,
+        }
+    }
+
+    fn name(&self)->&'static str {
+        match self {
+            Self::None => "none",
+            Self::Gzip => "gzip",
+            Self::Zstd => "zstd",
+        }
+    }
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportColumn {
+    pub name: String,
+    pub mysql_type: String,
+    // the parquet (duckdb) type, csv and jsonl readers can use it as well
+    #[serde(rename = "type")]
+    pub export_type: String,
+    // binary columns are written as hex
+    pub hex: bool,
+}
+
+// one line of {basedir}/{year}/manifest.jsonl per exported file
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct ExportEntry {
+    pub table: String,
+    pub file: String,
+    pub format: String,
+    pub compression: String,
+    pub rows: u64,
+    pub columns: Vec<ExportColumn>,
+    pub file_sha256: String,
+    pub file_size: u64,
+    pub exported_at: String,
+}
+
+impl Row for ExportEntry {
+    const COLUMNS: &'static [&'static str] = &["table", "file", "format", "compression", "rows", "file_size"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.table.clone(),
+            self.file.clone(),
+            self.format.clone(),
+            self.compression.clone(),
+            self.rows.to_string(),
+            self.file_size.to_string(),
+        ]
+    }
+}
+
+pub fn manifest_path(outdir:&str)->String {
+    format!("{outdir}/manifest.jsonl")
+}
+
+pub fn export_type(col:&ColumnInfo)->String {
+    let unsigned = col.column_type.contains("unsigned");
+    match col.data_type.as_str() {
+        "bigint" if unsigned => "UBIGINT".into(),
+        "tinyint" | "smallint" | "mediumint" | "int" | "bigint" => "BIGINT".into(),
+        "year" => "INTEGER".into(),
+        "bit" => "UBIGINT".into(),
+        "float" => "FLOAT".into(),
+        "double" => "DOUBLE".into(),
+        "decimal" => {
+            let digits = col.column_type.trim_start_matches("decimal")
+                .trim_end_matches(" unsigned")
+                .trim_matches(|c| c == '(' || c == ')')
+                .to_string();
+            let precision = digits.split(',').next().and_then(|p| p.trim().parse::<u32>().ok());
+            match precision {
+                Some(p) if p <= 38 => format!("DECIMAL({digits})"),
+                _ => "DOUBLE".into(),
+            }
+        }
+        "date" => "DATE".into(),
+        "datetime" | "timestamp" => "TIMESTAMP".into(),
+        _ => "VARCHAR".into(),
+    }
+}
+
+fn select_expr(col:&ColumnInfo)->String {
+    let name = quote_ident(&col.name);
+    let value = if col.is_binary() {
+        format!("HEX({name})")
+    } else if col.data_type == "bit" {
+        format!("{name}+0")
+    } else {
+        name.clone()
+    };
+    format!("ISNULL({name}), {value}")
+}
+
+fn write_row(out:&mut impl Write, format:ExportFormat, cols:&[ColumnInfo], values:&[Option<String>])->std::io::Result<()> {
+    match format {
+        ExportFormat::Csv | ExportFormat::Parquet => {
+            // NULL is an empty field, the empty string is quoted
+            let line = values.iter()
+                .map(|v| match v.as_deref() {
+                    None => String::new(),
+                    Some("") => "\"\"".into(),
+                    Some(v) => csv_field(v),
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            writeln!(out, "{line}")
+        }
+        ExportFormat::Jsonl => {
+            let fields = cols.iter().zip(values)
+                .map(|(col, v)| {
+                    let value = match v {
+                        None => "null".into(),
+                        Some(v) if col.is_numeric() => v.clone(),
+                        Some(v) => serde_json::to_string(v).expect("serialize string"),
+                    };
+                    format!("{}:{value}", serde_json::to_string(&col.name).expect("serialize string"))
+                })
+                .collect::<Vec<_>>()
+                .join(",");
+            writeln!(out, "{{{fields}}}")
+        }
+    }
+}
+
+// the ISNULL, value pairs of `mysql -B` into the file, returns the rows written
+fn stream(input:impl BufRead, out:&mut impl Write, format:ExportFormat, cols:&[ColumnInfo])->std::io::Result<u64> {
+    if format != ExportFormat::Jsonl {
+        let header = cols.iter().map(|c| csv_field(&c.name)).collect::<Vec<_>>().join(",");
+        writeln!(out, "{header}")?;
+    }
+    let mut rows = 0;
+    for line in input.split(b'\n') {
+        let line = line?;
+        let line = String::from_utf8_lossy(&line);
+        let fields = line.split('\t').collect::<Vec<_>>();
+        let values = fields.chunks(2)
+            .map(|pair| (pair[0] != "1").then(|| batch_field(pair.get(1).unwrap_or(&""))))
+            .collect::<Vec<_>>();
+        write_row(out, format, cols, &values)?;
+        rows += 1;
+    }
+    out.flush()?;
+    Ok(rows)
+}
+
+fn run(cmd:&mut Command)->Result<(), String> {
+    eprintln!("--------- {cmd:?} -----------");
+    match cmd.status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("{cmd:?} finished with: {status}")),
+        Err(err) => Err(format!("{cmd:?} failed: {err}")),
+    }
+}
+
+// parquet is written by the duckdb CLI, which must be on PATH
+pub fn require_duckdb()->Result<(), String> {
+    match Command::new("duckdb").arg("-version").stdout(Stdio::null()).status() {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(format!("duckdb -version finished with: {status}")),
+        Err(err) => Err(format!("parquet needs the duckdb CLI on PATH: {err}")),
+    }
+}
+
+// the csv written by write is streamed into duckdb, which converts it as it reads
+fn to_parquet(cols:&[ColumnInfo], outfile:&str, compression:Compression,
+    write:impl FnOnce(&mut BufWriter<ChildStdin>)->Result<u64, String>)->Result<u64, String> {
+    let quote = |s:&str| s.replace('\'', "''");
+    let columns = cols.iter()
+        .map(|col| format!("'{}': '{}'", quote(&col.name), export_type(col)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let codec = match compression {
+        Compression::None => "uncompressed",
+        other => other.name(),
+    };
+    let sql = format!("COPY (SELECT * FROM read_csv('/dev/stdin', header=true, allow_quoted_nulls=false, columns={{{columns}}})) \
+        TO '{}' (FORMAT parquet, COMPRESSION {codec})", quote(outfile));
+    let mut cmd = Command::new("duckdb");
+    cmd.arg("-c").arg(sql).stdin(Stdio::piped());
+    eprintln!("--------- {cmd:?} -----------");
+    let mut child = cmd.spawn().map_err(|e| format!("duckdb: {e}"))?;
+    let mut stdin = BufWriter::new(child.stdin.take().expect("piped stdin"));
+    let written = write(&mut stdin);
+    drop(stdin);
+    let status = child.wait().map_err(|e| format!("duckdb: {e}"))?;
+    let rows = written?;
+    if !status.success() {
+        return Err(format!("duckdb finished with: {status}"));
+    }
+    Ok(rows)
+}
+
+// the rows of the table in format into out, returns the rows written
+fn read_table(dbe:&DatabaseEnv, table:&str, cols:&[ColumnInfo], out:&mut impl Write, format:ExportFormat)->Result<u64, String> {
+    let select = cols.iter().map(select_expr).collect::<Vec<_>>().join(", ");
+    let mut child = exe_sql_spawn(dbe, &format!("SELECT {select} FROM {table}"));
+    let stdout = child.stdout.take().expect("piped stdout");
+    let streamed = stream(BufReader::new(stdout), out, format, cols)
+        .map_err(|e| format!("writing {table}: {e}"));
+    let status = child.wait().map_err(|e| format!("reading {table}: {e}"))?;
+    let rows = streamed?;
+    if !status.success() {
+        return Err(format!("reading {table} finished with: {status}"));
+    }
+    Ok(rows)
+}
+
+// streams the table into {outdir}/{table}.{ext}, then appends the manifest entry,
+// parquet needs require_duckdb first
+pub fn export(dbe:&DatabaseEnv, table:&str, outdir:&str, format:ExportFormat, compression:Compression)->Result<ExportEntry, String> {
+    let cols = column_info(dbe, table);
+    if cols.is_empty() {
+        return Err(format!("{table} does not exist"));
+    }
+    std::fs::create_dir_all(outdir).map_err(|e| format!("{outdir}: {e}"))?;
+    let mut file = format!("{outdir}/{table}.{}", format.ext());
+    let rows = match format {
+        ExportFormat::Parquet => to_parquet(&cols, &file, compression, |out| read_table(dbe, table, &cols, out, format))?,
+        _ => {
+            let mut out = BufWriter::new(File::create(&file).map_err(|e| format!("{file}: {e}"))?);
+            read_table(dbe, table, &cols, &mut out, format)?
+        }
+    };
+
+    match (format, compression) {
+        (ExportFormat::Parquet, _) | (_, Compression::None) => {}
+        (_, Compression::Gzip) => {
+            run(Command::new("gzip").arg("-f").arg(&file))?;
+            file.push_str(".gz");
+        }
+        (_, Compression::Zstd) => {
+            run(Command::new("zstd").arg("-q").arg("-f").arg("--rm").arg(&file))?;
+            file.push_str(".zst");
+        }
+    }
+
+    let entry = ExportEntry {
+        table: table.to_string(),
+        file: file.clone(),
+        format: format.ext().into(),
+        compression: compression.name().into(),
+        rows,
+        columns: cols.iter()
+            .map(|col| ExportColumn {
+                name: col.name.clone(),
+                mysql_type: col.column_type.clone(),
+                export_type: export_type(col),
+                hex: col.is_binary(),
+            })
+            .collect(),
+        file_sha256: file_sha256(&file).ok_or_else(|| format!("failed to hash {file}"))?,
+        file_size: std::fs::metadata(&file).map_err(|e| format!("{file}: {e}"))?.len(),
+        exported_at: clock::now_stamp(),
+    };
+    let mut manifest = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(manifest_path(outdir))
+        .map_err(|e| format!("{}: {e}", manifest_path(outdir)))?;
+    let line = serde_json::to_string(&entry).expect("serialize export entry");
+    writeln!(manifest, "{line}").map_err(|e| format!("{}: {e}", manifest_path(outdir)))?;
+    Ok(entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn col(name:&str, data_type:&str, column_type:&str)->ColumnInfo {
+        ColumnInfo {
+            name: name.into(),
+            data_type: data_type.into(),
+            column_type: column_type.into(),
+            nullable: true,
+            max_length: None,
+            generated: false,
+        }
+    }
+
+    #[test]
+    fn types_and_rows() {
+        assert_eq!(export_type(&col("id", "bigint", "bigint(20) unsigned")), "UBIGINT");
+        assert_eq!(export_type(&col("amount", "decimal", "decimal(12,2)")), "DECIMAL(12,2)");
+        assert_eq!(export_type(&col("day", "datetime", "datetime")), "TIMESTAMP");
+        assert_eq!(export_type(&col("note", "varchar", "varchar(32)")), "VARCHAR");
+
+        let cols = vec![col("id", "int", "int(11)"), col("note", "varchar", "varchar(32)")];
+        let values = vec![Some("7".to_string()), None];
+        let mut out = Vec::new();
+        write_row(&mut out, ExportFormat::Jsonl, &cols, &values).unwrap();
+        write_row(&mut out, ExportFormat::Csv, &cols, &[Some("8".into()), Some(String::new())]).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "{\"id\":7,\"note\":null}\n8,\"\"\n");
+    }
+}
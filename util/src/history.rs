@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use super::report::{opt, Row, TableReport};
+
+// every `migrate count` run is appended as one json line to {basedir}/{database}-count-history.jsonl
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CountRun {
+    pub run: String,
+    pub database: String,
+    pub postfix: String,
+    pub counts: Vec<CountEntry>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct CountEntry {
+    pub table: String,
+    pub exists: bool,
+    pub rows: Option<u64>,
+}
+
+// rows per table, None when the table did not exist
+pub type Counts = BTreeMap<String, Option<u64>>;
+
+#[derive(Debug, PartialEq, serde::Serialize)]
+pub struct CountDiff {
+    pub table: String,
+    pub change: String,
+    pub before: Option<u64>,
+    pub after: Option<u64>,
+}
+
+impl Row for CountDiff {
+    const COLUMNS: &'static [&'static str] = &["table", "change", "before", "after"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.table.clone(),
+            self.change.clone(),
+            opt(self.before),
+            opt(self.after),
+        ]
+    }
+}
+
+impl CountRun {
+    pub fn new(run:&str, database:&str, postfix:&str, reports:&[TableReport])->Self {
+        Self {
+            run: run.into(),
+            database: database.into(),
+            postfix: postfix.into(),
+            counts: reports.iter()
+                .map(|r| CountEntry {
+                    table: r.table.clone(),
+                    exists: r.exists,
+                    rows: r.rows,
+                })
+                .collect(),
+        }
+    }
+
+    pub fn counts(&self)->Counts {
+        self.counts.iter()
+            .map(|e| (e.table.clone(), if e.exists { e.rows } else { None }))
+            .collect()
+    }
+}
+
+pub fn history_path(basedir:&str, database:&str)->String {
+    format!("{basedir}/{database}-count-history.jsonl")
+}
+
+pub fn load(path:&str)->Vec<CountRun> {
+    let Ok(content) = std::fs::read_to_string(path) else {
+        return Vec::new();
+    };
+    content.lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+pub fn append(path:&str, run:&CountRun)->std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)?;
+    let line = serde_json::to_string(run).expect("serialize count run");
+    writeln!(file, "{line}")
+}
+
+// `last` is the latest run with the same postfix, a run stamp picks that run,
+// otherwise a file written by count, either `table value` lines or --output json
+pub fn resolve(source:&str, runs:&[CountRun], postfix:&str)->Option<Counts> {
+    if source == "last" {
+        return runs.iter().rev().find(|r| r.postfix == postfix).map(CountRun::counts);
+    }
+    if let Some(run) = runs.iter().find(|r| r.run == source) {
+        return Some(run.counts());
+    }
+    let content = std::fs::read_to_string(source).ok()?;
+    if let Ok(reports) = serde_json::from_str::<Vec<TableReport>>(&content) {
+        return Some(reports.into_iter()
+            .map(|r| (r.table, if r.exists { r.rows } else { None }))
+            .collect());
+    }
+    Some(parse_txt(&content))
+}
+
+pub fn parse_txt(content:&str)->Counts {
+    content.lines()
+        .filter_map(|line| {
+            let (table, value) = line.split_once(' ').unwrap_or((line, ""));
+            let table = table.trim();
+            (!table.is_empty()).then(|| (table.to_string(), value.trim().parse().ok()))
+        })
+        .collect()
+}
+
+pub fn diff(before:&Counts, after:&Counts)->Vec<CountDiff> {
+    let mut tables = before.keys().chain(after.keys()).collect::<Vec<_>>();
+    tables.sort();
+    tables.dedup();
+    tables.into_iter()
+        .filter_map(|table| {
+            let b = before.get(table).copied().flatten();
+            let a = after.get(table).copied().flatten();
+            let change = match (b, a) {
+                (None, None) => return None,
+                (None, Some(_)) => "appeared",
+                (Some(_), None) => "disappeared",
+                (Some(b), Some(a)) if b == a => return None,
+                (Some(_), Some(_)) => "changed",
+            };
+            Some(CountDiff {
+                table: table.clone(),
+                change: change.into(),
+                before: b,
+                after: a,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff_counts() {
+        let before = parse_txt("t1701 10\nt1702 5\nt1703 \nt1704 7\n");
+        let after = parse_txt("t1701 10\nt1702 6\nt1703 1\nt1705 2\n");
+        let diff = diff(&before, &after);
+        let changes = diff.iter().map(|d| (d.table.as_str(), d.change.as_str())).collect::<Vec<_>>();
+        assert_eq!(changes, vec![
+            ("t1702", "changed"),
+            ("t1703", "appeared"),
+            ("t1704", "disappeared"),
+            ("t1705", "appeared"),
+        ]);
+        assert_eq!(diff[0].before, Some(5));
+        assert_eq!(diff[0].after, Some(6));
+    }
+}
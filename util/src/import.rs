@@ -0,0 +1,481 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::process::Command;
+
+use super::{count_rows, create_empty, table_exists};
+use super::export::{manifest_path, ExportEntry};
+use super::panelenv::DatabaseEnv;
+use super::precreate::latest_month;
+use super::report::{opt, Row};
+use super::schema::{column_info, ident_list, ColumnInfo};
+use super::session::Session;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImportFormat {
+    Csv,
+    Parquet,
+}
+
+impl ImportFormat {
+    pub fn parse(s:&str)->Option<Self> {
+        match s {
+            "csv" => Some(Self::Csv),
+            "parquet" => Some(Self::Parquet),
+            _ => None,
+        }
+    }
+
+    pub fn ext(&self)->&'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Parquet => "parquet",
+        }
+    }
+}
+
+pub struct ImportOptions<'a> {
+    // created LIKE this table when the target is missing, else LIKE the latest month of the name
+    pub template: Option<&'a str>,
+    // rows per INSERT
+    pub batch: usize,
+    // load into a table which already holds rows
+    pub force: bool,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct ImportReport {
+    pub file: String,
+    pub table: String,
+    pub rows: Option<u64>,
+    pub loaded: Option<u64>,
+    pub status: String,
+}
+
+impl Row for ImportReport {
+    const COLUMNS: &'static [&'static str] = &["file", "table", "rows", "loaded", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.file.clone(),
+            self.table.clone(),
+            opt(self.rows),
+            opt(self.loaded),
+            self.status.clone(),
+        ]
+    }
+}
+
+// RFC 4180 records, an unquoted empty field is NULL and a quoted one the empty string
+pub struct CsvReader<R:BufRead> {
+    bytes: std::iter::Peekable<std::io::Bytes<R>>,
+}
+
+impl<R:BufRead> CsvReader<R> {
+    pub fn new(input:R)->Self {
+        Self { bytes: input.bytes().peekable() }
+    }
+
+    fn field(buf:&mut Vec<u8>, quoted:bool)->Option<String> {
+        let field = String::from_utf8_lossy(buf).into_owned();
+        buf.clear();
+        (quoted || !field.is_empty()).then_some(field)
+    }
+}
+
+impl<R:BufRead> Iterator for CsvReader<R> {
+    type Item = std::io::Result<Vec<Option<String>>>;
+
+    fn next(&mut self)->Option<Self::Item> {
+        self.bytes.peek()?;
+        let mut record = Vec::new();
+        let mut buf = Vec::new();
+        let mut quoted = false;
+        let mut in_quotes = false;
+        while let Some(b) = self.bytes.next() {
+            let b = match b {
+                Ok(b) => b,
+                Err(err) => return Some(Err(err)),
+            };
+            match (in_quotes, b) {
+                (true, b'"') if matches!(self.bytes.peek(), Some(Ok(b'"'))) => {
+                    self.bytes.next();
+                    buf.push(b'"');
+                }
+                (true, b'"') => in_quotes = false,
+                (true, _) => buf.push(b),
+                (false, b'"') => {
+                    in_quotes = true;
+                    quoted = true;
+                }
+                (false, b',') => {
+                    record.push(Self::field(&mut buf, quoted));
+                    quoted = false;
+                }
+                (false, b'\r') if matches!(self.bytes.peek(), Some(Ok(b'\n'))) => {}
+                (false, b'\n') => break,
+                (false, _) => buf.push(b),
+            }
+        }
+        record.push(Self::field(&mut buf, quoted));
+        Some(Ok(record))
+    }
+}
+
+fn digits(s:&str)->bool {
+    !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit())
+}
+
+// yyyy-mm-dd of a day the calendar has
+fn is_date(s:&str)->bool {
+    let mut parts = s.split('-');
+    let (Some(y), Some(m), Some(d), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+        return false;
+    };
+    if y.len() != 4 || m.len() != 2 || d.len() != 2 || ![y, m, d].iter().all(|p| digits(p)) {
+        return false;
+    }
+    let (y, m, d) = (y.parse::<u32>().unwrap(), m.parse::<u32>().unwrap(), d.parse::<u32>().unwrap());
+    let leap = y.is_multiple_of(4) && (!y.is_multiple_of(100) || y.is_multiple_of(400));
+    let days = match m {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    (1..=days).contains(&d)
+}
+
+// hh:mm:ss with up to six fraction digits
+fn is_time(s:&str)->bool {
+    let (hms, fraction) = s.split_once('.').unwrap_or((s, "0"));
+    let parts = hms.split(':').collect::<Vec<_>>();
+    let [h, m, sec] = parts.as_slice() else {
+        return false;
+    };
+    let field = |p:&str, max:u32| p.len() == 2 && digits(p) && p.parse::<u32>().unwrap() <= max;
+    field(h, 23) && field(m, 59) && field(sec, 59) && fraction.len() <= 6 && digits(fraction)
+}
+
+// the values of an integer column, by its type and UNSIGNED
+fn integer_range(col:&ColumnInfo)->(i128, i128) {
+    let bits = match col.data_type.as_str() {
+        "tinyint" => 8,
+        "smallint" => 16,
+        "mediumint" => 24,
+        "int" => 32,
+        _ => 64,
+    };
+    if col.column_type.contains("unsigned") {
+        (0, (1 << bits) - 1)
+    } else {
+        (-(1 << (bits - 1)), (1 << (bits - 1)) - 1)
+    }
+}
+
+// plain notation within the precision and scale of decimal(p,s), as mysql prints it
+fn fits_decimal(col:&ColumnInfo, v:&str)->bool {
+    let (precision, scale) = col.column_type.split_once('(')
+        .and_then(|(_, args)| args.split_once(')'))
+        .map(|(args, _)| args.split_once(',').unwrap_or((args, "0")))
+        .and_then(|(p, s)| Some((p.trim().parse::<usize>().ok()?, s.trim().parse::<usize>().ok()?)))
+        .unwrap_or((10, 0));
+    let unsigned = col.column_type.contains("unsigned");
+    let number = match v.strip_prefix('-') {
+        Some(_) if unsigned => return false,
+        Some(number) => number,
+        None => v.strip_prefix('+').unwrap_or(v),
+    };
+    let (int, fraction) = number.split_once('.').unwrap_or((number, ""));
+    (digits(int) || digits(fraction))
+        && [int, fraction].iter().all(|p| p.bytes().all(|c| c.is_ascii_digit()))
+        && int.trim_start_matches('0').len() <= precision.saturating_sub(scale)
+        && fraction.len() <= scale
+}
+
+// why the value does not fit the column, None when it does;
+// a hex value is the export of a binary column and is measured by the bytes it decodes to
+pub fn invalid(col:&ColumnInfo, value:Option<&str>, hex:bool)->Option<String> {
+    let Some(v) = value else {
+        return (!col.nullable).then(|| "NULL in a NOT NULL column".into());
+    };
+    if hex {
+        if v.len() % 2 != 0 || !v.bytes().all(|c| c.is_ascii_hexdigit()) {
+            return Some(format!("{v:?} is not hex encoded"));
+        }
+        let ok = col.max_length.is_none_or(|max| v.len() as u64 / 2 <= max);
+        return (!ok).then(|| format!("{} bytes do not fit {}", v.len() / 2, col.column_type));
+    }
+    let ok = match col.data_type.as_str() {
+        "year" => v.parse::<u32>().is_ok_and(|y| y == 0 || (1901..=2155).contains(&y)),
+        _ if col.is_integer() => {
+            let (min, max) = integer_range(col);
+            v.parse::<i128>().is_ok_and(|n| (min..=max).contains(&n))
+        }
+        "decimal" => fits_decimal(col, v),
+        "float" | "double" => v.parse::<f64>().is_ok_and(f64::is_finite),
+        "date" => is_date(v),
+        "datetime" | "timestamp" => {
+            let (date, time) = v.split_once([' ', 'T']).unwrap_or((v, "00:00:00"));
+            is_date(date) && is_time(time)
+        }
+        _ => col.max_length.is_none_or(|max| v.chars().count() as u64 <= max),
+    };
+    (!ok).then(|| format!("{v:?} is not a valid {}", col.column_type))
+}
+
+fn literal(value:Option<&str>, hex:bool)->String {
+    match value {
+        None => "NULL".into(),
+        Some(v) if hex => format!("UNHEX('{}')", v.replace('\'', "''")),
+        Some(v) => format!("'{}'", v.replace('\\', r"\\").replace('\'', "''")),
+    }
+}
+
+// the header is mapped by name onto the table columns
+fn header_columns(header:&[Option<String>], cols:&[ColumnInfo])->Result<Vec<ColumnInfo>, String> {
+    header.iter()
+        .map(|name| {
+            let name = name.as_deref().unwrap_or_default();
+            cols.iter()
+                .find(|c| c.name.eq_ignore_ascii_case(name))
+                .cloned()
+                .ok_or_else(|| format!("the table has no column {name:?}"))
+        })
+        .collect()
+}
+
+fn open(path:&str)->Result<CsvReader<BufReader<File>>, String> {
+    File::open(path)
+        .map(|file| CsvReader::new(BufReader::new(file)))
+        .map_err(|e| format!("{path}: {e}"))
+}
+
+// every record is checked before anything is loaded, returns the rows of the file
+fn validate(path:&str, cols:&[ColumnInfo], hex:&[String])->Result<(Vec<ColumnInfo>, u64), String> {
+    let mut records = open(path)?;
+    let header = records.next().ok_or_else(|| format!("{path} is empty"))?.map_err(|e| e.to_string())?;
+    let mapped = header_columns(&header, cols)?;
+    let hex = mapped.iter().map(|c| hex.contains(&c.name)).collect::<Vec<_>>();
+    let mut rows = 0;
+    for (i, record) in records.enumerate() {
+        let record = record.map_err(|e| e.to_string())?;
+        let line = i + 2;
+        if record.len() != mapped.len() {
+            return Err(format!("record {line} has {} fields, the header {}", record.len(), mapped.len()));
+        }
+        for ((col, value), hex) in mapped.iter().zip(&record).zip(&hex) {
+            if let Some(reason) = invalid(col, value.as_deref(), *hex) {
+                return Err(format!("record {line}, column {}: {reason}", col.name));
+            }
+        }
+        rows += 1;
+    }
+    Ok((mapped, rows))
+}
+
+fn count(session:&mut Session, table:&str)->Result<u64, String> {
+    session.row(&format!("SELECT COUNT(*) FROM {table}"))?
+        .first()
+        .and_then(|n| n.parse().ok())
+        .ok_or_else(|| format!("failed to count {table}"))
+}
+
+// every batch runs in one transaction, committed only when the table gained exactly the rows
+// of the file, so a failed import leaves the table as it was; returns the rows loaded
+fn load(session:&mut Session, path:&str, table:&str, mapped:&[ColumnInfo], hex:&[String], batch:usize, rows:u64)->Result<u64, String> {
+    let names = mapped.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+    let hex = mapped.iter().map(|c| hex.contains(&c.name)).collect::<Vec<_>>();
+    let insert = format!("INSERT INTO {table} ({}) VALUES ", ident_list(&names));
+    if session.execute("START TRANSACTION", ";")?.failed {
+        return Err("failed to start a transaction".into());
+    }
+    let batches = |session:&mut Session| {
+        let before = count(session, table)?;
+        let mut records = open(path)?.skip(1).peekable();
+        let mut loaded = 0;
+        while records.peek().is_some() {
+            let values = records.by_ref()
+                .take(batch)
+                .map(|record| record.map(|record| {
+                    let row = record.iter().zip(&hex)
+                        .map(|(v, hex)| literal(v.as_deref(), *hex))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    format!("({row})")
+                }))
+                .collect::<std::io::Result<Vec<_>>>()
+                .map_err(|e| e.to_string())?;
+            if session.execute(&format!("{insert}{}", values.join(", ")), ";")?.failed {
+                return Err(format!("batch after {loaded} rows failed"));
+            }
+            loaded += values.len();
+            eprintln!("----- {table}: {loaded} rows loaded -----");
+        }
+        let gained = count(session, table)?.saturating_sub(before);
+        if gained != rows {
+            return Err(format!("count mismatch, {table} gained {gained} rows"));
+        }
+        Ok(gained)
+    };
+    match batches(session) {
+        Ok(loaded) if !session.execute("COMMIT", ";")?.failed => Ok(loaded),
+        Ok(_) => Err("commit failed".into()),
+        Err(err) => {
+            session.execute("ROLLBACK", ";")?;
+            Err(format!("{err}, rolled back"))
+        }
+    }
+}
+
+// the binary columns an export of this file wrote as hex
+fn hex_columns(path:&str)->Vec<String> {
+    let dir = std::path::Path::new(path).parent().and_then(|p| p.to_str()).unwrap_or(".");
+    let content = std::fs::read_to_string(manifest_path(dir)).unwrap_or_default();
+    content.lines()
+        .filter_map(|line| serde_json::from_str::<ExportEntry>(line).ok())
+        .rfind(|entry| entry.file == path)
+        .map(|entry| entry.columns.into_iter().filter(|c| c.hex).map(|c| c.name).collect())
+        .unwrap_or_default()
+}
+
+// the csv a parquet file is converted to, removed however the import ends
+struct TempCsv(String);
+
+impl Drop for TempCsv {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+// written by the duckdb CLI, see export::require_duckdb
+fn parquet_to_csv(path:&str, csvfile:&str)->Result<(), String> {
+    let quote = |s:&str| s.replace('\'', "''");
+    let sql = format!("COPY (SELECT * FROM read_parquet('{}')) TO '{}' (FORMAT csv, HEADER)", quote(path), quote(csvfile));
+    let status = Command::new("duckdb").arg("-c").arg(sql).status().map_err(|e| format!("duckdb: {e}"))?;
+    if !status.success() {
+        return Err(format!("duckdb finished with: {status}"));
+    }
+    Ok(())
+}
+
+// the file is validated as a whole and loaded in batches of one transaction, which commits only
+// when the rows the table gained match it; a table which holds rows is refused unless forced,
+// parquet needs export::require_duckdb first
+pub fn import(dbe:&DatabaseEnv, path:&str, format:ImportFormat, table:&str, name:&str, options:&ImportOptions)->ImportReport {
+    let mut report = ImportReport {
+        file: path.to_string(),
+        table: table.to_string(),
+        rows: None,
+        loaded: None,
+        status: String::new(),
+    };
+    let csvfile = match format {
+        ImportFormat::Csv => path.to_string(),
+        ImportFormat::Parquet => format!("{path}.import.csv"),
+    };
+    let _temp = (format == ImportFormat::Parquet).then(|| TempCsv(csvfile.clone()));
+    let result = (|| {
+        if !std::path::Path::new(path).exists() {
+            return Err("no file".to_string());
+        }
+        if !table_exists(dbe, table) {
+            let template = options.template.map(String::from).or_else(|| latest_month(dbe, name))
+                .ok_or_else(|| format!("{table} does not exist and there is no template"))?;
+            if !create_empty(dbe, &template, table).success() {
+                return Err(format!("create {table} like {template} failed"));
+            }
+        }
+        if !options.force && count_rows(dbe, table).ok_or("count before loading failed")? > 0 {
+            return Err(format!("{}.{table} is not empty, use --force", dbe.database));
+        }
+        if format == ImportFormat::Parquet {
+            parquet_to_csv(path, &csvfile)?;
+        }
+        let hex = hex_columns(path);
+        let (mapped, rows) = validate(&csvfile, &column_info(dbe, table), &hex)?;
+        report.rows = Some(rows);
+        let mut session = Session::open(dbe)?;
+        report.loaded = Some(load(&mut session, &csvfile, table, &mapped, &hex, options.batch, rows)?);
+        session.close();
+        Ok(())
+    })();
+    report.status = match result {
+        Ok(()) => "imported".into(),
+        Err(reason) => reason,
+    };
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn number_ranges() {
+        let col = |data_type:&str, column_type:&str| ColumnInfo {
+            name: "c".into(),
+            data_type: data_type.into(),
+            column_type: column_type.into(),
+            nullable: true,
+            max_length: None,
+            generated: false,
+        };
+        let tiny = col("tinyint", "tinyint(4)");
+        assert!(invalid(&tiny, Some("-128"), false).is_none());
+        assert!(invalid(&tiny, Some("128"), false).is_some());
+        let unsigned = col("int", "int(10) unsigned");
+        assert!(invalid(&unsigned, Some("4294967295"), false).is_none());
+        assert!(invalid(&unsigned, Some("-1"), false).is_some());
+        assert!(invalid(&col("bigint", "bigint(20)"), Some("9223372036854775808"), false).is_some());
+        assert!(invalid(&col("year", "year(4)"), Some("1900"), false).is_some());
+        let price = col("decimal", "decimal(5,2)");
+        assert!(invalid(&price, Some("-123.45"), false).is_none());
+        assert!(invalid(&price, Some("0.5"), false).is_none());
+        assert!(invalid(&price, Some("1234.5"), false).is_some());
+        assert!(invalid(&price, Some("1.234"), false).is_some());
+        assert!(invalid(&price, Some("NaN"), false).is_some());
+        assert!(invalid(&price, Some("1e2"), false).is_some());
+        assert!(invalid(&price, Some("."), false).is_some());
+    }
+
+    #[test]
+    fn csv_records() {
+        let input = "id,note\r\n1,\"a,\"\"b\"\"\nc\"\n2,\n3,\"\"\n";
+        let records = CsvReader::new(input.as_bytes()).collect::<std::io::Result<Vec<_>>>().unwrap();
+        assert_eq!(records, vec![
+            vec![Some("id".into()), Some("note".into())],
+            vec![Some("1".into()), Some("a,\"b\"\nc".into())],
+            vec![Some("2".into()), None],
+            vec![Some("3".into()), Some(String::new())],
+        ]);
+    }
+
+    #[test]
+    fn validation() {
+        let col = |data_type:&str, nullable| ColumnInfo {
+            name: "c".into(),
+            data_type: data_type.into(),
+            column_type: data_type.into(),
+            nullable,
+            max_length: Some(3),
+            generated: false,
+        };
+        assert!(invalid(&col("int", false), Some("-12"), false).is_none());
+        assert!(invalid(&col("int", false), Some("1.5"), false).is_some());
+        assert!(invalid(&col("int", false), None, false).is_some());
+        assert!(invalid(&col("datetime", true), Some("2017-01-31 23:59:59"), false).is_none());
+        assert!(invalid(&col("date", true), Some("2017/01/31"), false).is_some());
+        assert!(invalid(&col("varchar", true), Some("abcd"), false).is_some());
+        assert!(invalid(&col("date", true), Some("2016-02-29"), false).is_none());
+        assert!(invalid(&col("date", true), Some("2017-02-29"), false).is_some());
+        assert!(invalid(&col("date", true), Some("2017-02-31"), false).is_some());
+        assert!(invalid(&col("datetime", true), Some("2017-01-31 24:00:00"), false).is_some());
+        assert!(invalid(&col("datetime", true), Some("2017-01-31 23:59:59.123456"), false).is_none());
+        assert!(invalid(&col("float", true), Some("NaN"), false).is_some());
+        assert!(invalid(&col("double", true), Some("inf"), false).is_some());
+        assert!(invalid(&col("double", true), Some("-1.5e3"), false).is_none());
+        assert!(invalid(&col("varbinary", true), Some("00ff10"), true).is_none());
+        assert!(invalid(&col("varbinary", true), Some("00ff1020"), true).is_some());
+        assert!(invalid(&col("varbinary", true), Some("0g"), true).is_some());
+    }
+}
@@ -2,66 +2,70 @@ use std::
     process::Command
 ;
 use std::io::Write;
+use std::process::Child;
 use std::process::ExitStatus;
 use std::process::Output;
+use std::process::Stdio;
 
 mod cfg;
 mod panelenv;
+pub mod alter;
+pub mod backup;
+pub mod catalog;
+pub mod clock;
+pub mod compare;
+pub mod confirm;
+pub mod dump;
+pub mod export;
+pub mod consolidate;
+pub mod history;
+pub mod import;
+pub mod pipeline;
+pub mod plan;
+pub mod prune;
+pub mod precreate;
+pub mod report;
+pub mod rowdiff;
+pub mod schema;
+pub mod schemacheck;
+pub mod session;
+pub mod snapshot;
+pub mod split;
+pub mod sqldump;
+pub mod sqlload;
+pub mod stats;
+pub mod trash;
+pub mod views;
+pub use backup::BackupEnv;
+pub use confirm::{Approval, ConfirmEnv};
+pub use dump::{DumpEnv, DumpOptions};
 pub use panelenv::DatabaseEnv;
+pub use sqlload::LoadEnv;
+pub use panelenv::PanelEnv;
+pub use panelenv::TrashEnv;
 pub use panelenv::TableRule;
+pub use panelenv::TableHandle;
+pub use panelenv::TableHandleMut;
 pub use panelenv::load_panel_env;
 
 
-pub enum DropConfirmEnum {
-    DropFist,
-    DropAgain,
-    DropAll,
-    DropWarn,
-}
-impl DropConfirmEnum {
-    const fn as_n(&self)->usize {
-        match self {
-            DropConfirmEnum::DropFist => 0,
-            DropConfirmEnum::DropAgain => 1,
-            DropConfirmEnum::DropAll => 2,
-            DropConfirmEnum::DropWarn => 3,
-        }
+// the whole batch is listed and confirmed once, protected tables are never dropped
+pub fn drop_with_confirm(dbw:&DatabaseEnv, tables:&[String], confirm:&ConfirmEnv, approval:&Approval, trash:&TrashEnv)->ExitStatus {
+    let action = if trash.enabled { "TRASH" } else { "DROP" };
+    if !confirm.confirm_batch(&dbw.database, action, tables, approval) {
+        return exit_status(1);
     }
-    pub const fn from_usize(n:usize)->DropConfirmEnum {
-        match n {
-            0 => DropConfirmEnum::DropFist,
-            1 => DropConfirmEnum::DropAgain,
-            2 => DropConfirmEnum::DropAll,
-            _ => DropConfirmEnum::DropWarn,
+    for table in tables {
+        let status = if trash.enabled {
+            trash::soft_drop(dbw, trash, table)
+        } else {
+            exe_sql(dbw,&drop_sql(table))
+        };
+        if !status.success() {
+            return status;
         }
     }
-    const fn msg(&self)->(&'static str, &'static str) {
-        const MSG: [(&'static str, &'static str); 4] = [
-            ("You will \x1b[31mDROP\x1b[0m TABLE [\x1b[31m{table}\x1b[0m]?, input \x1b[31mDROP\x1b[0m to confirm: ", "DROP"),
-            ("You will \x1b[31mDROP\x1b[0m TABLE [\x1b[31m{table}\x1b[0m]?, input \x1b[31mDROP\x1b[0m to confirm: ", "DROP"),
-            ("You will \x1b[31mDROP\x1b[0m TABLE [\x1b[31m{table}\x1b[0m]?, input \x1b[31mDROP\x1b[0m to confirm: ", "DROP"),
-            ("You will \x1b[31mDROP\x1b[0m TABLE [\x1b[31m{table}\x1b[0m]!!!","")
-        ];
-        let n = self.as_n();
-        assert!(/*n >= 0 &&*/ n < MSG.len());
-        MSG[n]
-    }
-}
-pub fn drop_with_confirm(dbw:&DatabaseEnv, table:&str, confirm:DropConfirmEnum)->ExitStatus {
-    let sql = format!("DROP TABLE {table};");
-    let (msg, confirm_str) = confirm.msg();
-    let msg = msg.replace("{table}", table);
-    println!("{msg}");
-    std::io::stdout().flush().unwrap();
-    if let DropConfirmEnum::DropWarn = confirm {} else {
-        let mut input = String::new();
-        std::io::stdin()
-            .read_line(&mut input)
-            .expect("read input failly");
-        let input = input.trim_end();
-        assert_eq!(input,confirm_str);
-    }
-    exe_sql(dbw,&sql)
+    exit_status(0)
 }
 
 pub fn copy(dbw:&DatabaseEnv, table:&str, table_new:&str)->ExitStatus {
@@ -74,8 +78,16 @@ pub fn copy(dbw:&DatabaseEnv, table:&str, table_new:&str)->ExitStatus {
     exe_sql(dbw,&insert_data_sql)
 }
 
+pub fn drop_sql(table:&str)->String {
+    format!("DROP TABLE {table};")
+}
+
+pub fn create_empty_sql(src_table:&str, empty_table:&str)->String {
+    format!("create table {empty_table} like {src_table}")
+}
+
 pub fn create_empty(dbe:&DatabaseEnv, src_table:&str, empty_table:&str)->ExitStatus {
-    let sql = format!("create table {empty_table} like {src_table}");
+    let sql = create_empty_sql(src_table, empty_table);
     exe_sql(dbe,&sql)
 }
 
@@ -83,13 +95,13 @@ pub fn remove_postfix(dbe:&DatabaseEnv, table:&str, postfix:&str)->ExitStatus {
     assert!(table.ends_with(postfix));
     let src = table;
     let dst = table.strip_suffix(postfix).unwrap();
-    rename(dbe, &[(&src, &dst)])
+    rename(dbe, &[(src, dst)])
 }
 
 pub fn add_postfix(dbe:&DatabaseEnv, table:&str, postfix:&str)->ExitStatus {
     let src = table;
     let dst = format!("{table}{postfix}");
-    rename(dbe, &[(&src, &dst)])
+    rename(dbe, &[(src, &dst)])
 }
 
 pub fn is_empty(dbe:&DatabaseEnv, table:&str)->bool {
@@ -97,11 +109,18 @@ pub fn is_empty(dbe:&DatabaseEnv, table:&str)->bool {
     let output = exe_sql_with_output(dbe,&sql);
     assert!(output.status.success());
     let stdout = output.stdout.trim_ascii_end();
-    println!("------------ {stdout:?} -----------");
+    eprintln!("------------ {stdout:?} -----------");
     assert!(stdout.len()==1);
     stdout[0] == b'0'
 }
 
+pub fn table_exists(dbe:&DatabaseEnv, table:&str)->bool {
+    let sql = format!("SELECT COUNT(*) FROM information_schema.TABLES WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}'");
+    let output = exe_sql_with_output(dbe,&sql);
+    assert!(output.status.success());
+    output.stdout.trim_ascii_end() != b"0"
+}
+
 pub fn count(dbe:&DatabaseEnv, table:&str)->(ExitStatus,String) {
     let sql = format!("select count(*) from {table}");
     let output = exe_sql_with_output(dbe,&sql);
@@ -110,9 +129,21 @@ pub fn count(dbe:&DatabaseEnv, table:&str)->(ExitStatus,String) {
     (status,stdout.to_string())
 }
 
+pub fn count_rows(dbe:&DatabaseEnv, table:&str)->Option<u64> {
+    let (status, out) = count(dbe, table);
+    if !status.success() {
+        return None;
+    }
+    out.trim().parse().ok()
+}
+
+pub fn rename_sql(src:&str, dst:&str)->String {
+    format!("alter table {src} rename to {dst}")
+}
+
 pub fn rename(dbe:&DatabaseEnv, src_dst:&[(&str,&str)])->ExitStatus {
     let sql = src_dst.iter()
-        .map(|(src, dst)| format!("alter table {src} rename to {dst}"))
+        .map(|(src, dst)| rename_sql(src, dst))
         .collect::<Vec<_>>()
         .join("; ");
     exe_sql(dbe,&sql)
@@ -126,7 +157,8 @@ pub fn zip(basedir:&str,year:&str,name:&str)->ExitStatus {
     // for wildcard * is expanded by shell not by zip,
     // we should put zip in a sh shell
     // because Command's parent is not a shell, but a bin built by rustc 
-    let zipcmd = format!("zip {zipfile} {zipsrc}");
+    // partial dumps are no backups, the quoted pattern is matched by zip
+    let zipcmd = format!("zip {zipfile} {zipsrc} -x '*.partial.sql'");
     let mut cmd = Command::new("sh");
     cmd.arg("-c").arg(zipcmd);
     println!("--------- {cmd:?} -----------");
@@ -138,6 +170,83 @@ pub fn zip(basedir:&str,year:&str,name:&str)->ExitStatus {
 }
 
 pub fn dump_out(env_rw: &DatabaseEnv, table:&str, outdir:&str)->ExitStatus {
+    dump_out_with(env_rw, table, outdir, &DumpOptions::default())
+}
+
+// a complete dump is {table}.sql with a {table}.sql.meta of the rows and checksum the file holds;
+// a filtered, schema or data dump goes to {table}.partial.sql and leaves both untouched
+pub fn dump_out_with(env_rw: &DatabaseEnv, table:&str, outdir:&str, options:&DumpOptions)->ExitStatus {
+    let table_out = format!("{outdir}/{table}.sql");
+    if let Err(err) = std::fs::create_dir_all(outdir) {
+        eprintln!("----- failed to create {outdir}: {err} -----");
+        return exit_status(1);
+    }
+    println!("----- {}/{table} => {outdir} ------", env_rw.database);
+    if !options.is_complete() {
+        // never replaces the complete dump, nor its meta
+        let partial_out = format!("{outdir}/{table}.partial.sql");
+        return match dump_file(env_rw, table, &partial_out, options) {
+            Ok(_) => exit_status(0),
+            Err(err) => {
+                eprintln!("----- {err} -----");
+                exit_status(1)
+            }
+        };
+    }
+    let result = complete_dump(env_rw, table, &table_out, options)
+        .and_then(|(rows, checksum, kind)| backup::write_meta(table, outdir, rows, &checksum, kind)
+            .ok_or_else(|| format!("failed to record {outdir}/{table}.sql.meta")));
+    match result {
+        Ok(_) => exit_status(0),
+        Err(err) => {
+            eprintln!("----- {err} -----");
+            exit_status(1)
+        }
+    }
+}
+
+// the rows and checksum of exactly what the file holds, read without locking
+// the table unless mysqldump locks it anyway
+fn complete_dump(env_rw: &DatabaseEnv, table:&str, table_out:&str, options:&DumpOptions)->Result<(u64, String, backup::ChecksumKind), String> {
+    if options.builtin.unwrap_or(false) {
+        let Some((rows, Some(checksum))) = dump_file(env_rw, table, table_out, options)? else {
+            return Err(format!("no checksum of {table} was read"));
+        };
+        return Ok((rows, checksum, backup::ChecksumKind::Rows));
+    }
+    if options.locks() {
+        // mysqldump takes the same READ lock, holding it alongside stalls no writer any longer
+        let mut session = session::Session::open(env_rw)?;
+        let (rows, checksum) = backup::locked_state(&mut session, table)?;
+        dump_file(env_rw, table, table_out, options)?;
+        session.close();
+        return Ok((rows, checksum, backup::ChecksumKind::Table));
+    }
+    // mysqldump reads its own snapshot in between, an unchanged state means it saw these rows
+    let before = backup::rows_state(env_rw, table)?;
+    dump_file(env_rw, table, table_out, options)?;
+    let after = backup::rows_state(env_rw, table)?;
+    if before != after {
+        return Err(format!("{table} changed while mysqldump ran, no meta is recorded for {table_out}"));
+    }
+    Ok((after.0, after.1, backup::ChecksumKind::Rows))
+}
+
+// the rows written and their snapshot checksum when the builtin dumper wrote the file, None for mysqldump
+fn dump_file(env_rw: &DatabaseEnv, table:&str, table_out:&str, options:&DumpOptions)->Result<Option<(u64, Option<String>)>, String> {
+    if options.builtin.unwrap_or(false) {
+        let (rows, checksum) = sqldump::dump(env_rw, table, table_out, options)?;
+        println!("----- {rows} rows written to {table_out} -----");
+        return Ok(Some((rows, checksum)));
+    }
+    let status = mysqldump(env_rw, table, options, table_out);
+    if !status.success() {
+        return Err(format!("mysqldump of {table} finished with: {status}"));
+    }
+    Ok(None)
+}
+
+fn mysqldump(env_rw: &DatabaseEnv, table:&str, options:&DumpOptions, outfile:&str)->ExitStatus {
     let url = &env_rw.url;
     let urlp = format!("-h{url}");
     let user = &env_rw.user;
@@ -145,10 +254,8 @@ pub fn dump_out(env_rw: &DatabaseEnv, table:&str, outdir:&str)->ExitStatus {
     let passwd = &env_rw.passwd;
     let passwdp = format!("-p{}",passwd);
     let database = &env_rw.database;
-    let table_out = format!("{outdir}/{table}.sql");
-    let mkoutdir = format!("mkdir -p {outdir}");
-    let mysqldump_cmd = format!("{mkoutdir}; mysqldump {urlp} {userp} {passwdp} {database} {table} > {table_out}");
-    println!("----- {database}/{table} => {outdir} ------");
+    let optionsp = options.args().join(" ");
+    let mysqldump_cmd = format!("mysqldump {urlp} {userp} {passwdp} {optionsp} {database} {table} > {outfile}");
     Command::new("sh")
         .arg("-c")
         .arg(mysqldump_cmd)
@@ -169,7 +276,7 @@ pub fn dump_in(env_rw: &DatabaseEnv, sqlfile:&str)->ExitStatus {
     let passwd_set = format!("export MYSQL_PWD={passwd}");
     // let passwdp = "";
     let mysql_cmd = format!("mysql {urlp} {userp} {databasep} < {sqlfile}");
-    let passwd_unset = format!("unset MYSQL_PWD");
+    let passwd_unset = "unset MYSQL_PWD";
     let cmd = format!("{passwd_set};{mysql_cmd};{passwd_unset};");
     let status = Command::new("sh")
         .arg("-c")
@@ -181,6 +288,33 @@ pub fn dump_in(env_rw: &DatabaseEnv, sqlfile:&str)->ExitStatus {
     status
 }
 
+// a field of `mysql -B` output, which escapes \0 \n \t and \\
+pub fn batch_field(s:&str)->String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('0') => out.push('\0'),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some('r') => out.push('\r'),
+            Some('b') => out.push('\u{8}'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+pub(crate) fn exit_status(code:i32)->ExitStatus {
+    use std::os::unix::process::ExitStatusExt;
+    ExitStatus::from_raw(code << 8)
+}
+
 pub fn exe_sql_with_output(env_rw: &DatabaseEnv, sql:&str)->Output {
     let url = &env_rw.url;
     let urlp = format!("-h{url}");
@@ -190,12 +324,11 @@ pub fn exe_sql_with_output(env_rw: &DatabaseEnv, sql:&str)->Output {
     // let passwdp = format!("-p{}",passwd);
     let database = &env_rw.database;
     let databasep  = format!("-D{}",database);
-    let sqlp = format!("-e'{sql}'");
-    println!("----- {sql} -----");
+    let sqlp = format!("-e'{}'", sql.replace('\'', r"'\''"));
+    eprintln!("----- {sql} -----");
     let passwd_set = format!("export MYSQL_PWD={passwd}");
-    let passwdp = "";
     let mysql_cmd = format!("mysql -A -NB {urlp} {userp} {databasep} {sqlp}");
-    let passwd_unset = format!("unset MYSQL_PWD");
+    let passwd_unset = "unset MYSQL_PWD";
     let cmd = format!("{passwd_set};{mysql_cmd};{passwd_unset};");
     // println!("cmd: {cmd}");
     let output = Command::new("sh")
@@ -204,9 +337,62 @@ pub fn exe_sql_with_output(env_rw: &DatabaseEnv, sql:&str)->Output {
         .output()
         .expect("failed to execute process");
     
-    println!("process finished with: {}",output.status);
+    eprintln!("process finished with: {}",output.status);
     output
 }
+// stdout is piped and streamed row by row, the caller waits for the child
+pub fn exe_sql_spawn(env_rw: &DatabaseEnv, sql:&str)->Child {
+    let url = &env_rw.url;
+    let urlp = format!("-h{url}");
+    let user = &env_rw.user;
+    let userp = format!("-u{}",user);
+    let passwd = &env_rw.passwd;
+    let database = &env_rw.database;
+    let databasep  = format!("-D{}",database);
+    let sqlp = format!("-e'{}'", sql.replace('\'', r"'\''"));
+    eprintln!("----- {sql} -----");
+    let passwd_set = format!("export MYSQL_PWD={passwd}");
+    let mysql_cmd = format!("mysql -A -NB --quick --default-character-set=utf8mb4 {urlp} {userp} {databasep} {sqlp}");
+    let cmd = format!("{passwd_set};exec {mysql_cmd}");
+    Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to execute process")
+}
+
+// the sql is written to the stdin of mysql, for statements too long for -e
+pub fn exe_sql_input(env_rw: &DatabaseEnv, sql:&str)->ExitStatus {
+    let url = &env_rw.url;
+    let urlp = format!("-h{url}");
+    let user = &env_rw.user;
+    let userp = format!("-u{}",user);
+    let passwd = &env_rw.passwd;
+    let database = &env_rw.database;
+    let databasep  = format!("-D{}",database);
+    eprintln!("----- {} bytes of sql -----", sql.len());
+    let passwd_set = format!("export MYSQL_PWD={passwd}");
+    let mysql_cmd = format!("mysql -A {urlp} {userp} {databasep}");
+    let cmd = format!("{passwd_set};exec {mysql_cmd}");
+    let mut child = Command::new("sh")
+        .arg("-c")
+        .arg(cmd)
+        .stdin(Stdio::piped())
+        .spawn()
+        .expect("failed to execute process");
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let written = stdin.write_all(sql.as_bytes());
+    drop(stdin);
+    let status = child.wait().expect("failed to wait on process");
+    if let Err(err) = written {
+        eprintln!("----- failed to send sql to mysql: {err} -----");
+        return exit_status(1);
+    }
+    eprintln!("process finished with: {status}");
+    status
+}
+
 pub fn exe_sql(env_rw: &DatabaseEnv, sql:&str)->ExitStatus {
     let url = &env_rw.url;
     let urlp = format!("-h{url}");
@@ -216,12 +402,11 @@ pub fn exe_sql(env_rw: &DatabaseEnv, sql:&str)->ExitStatus {
     // let passwdp = format!("-p{}",passwd);
     let database = &env_rw.database;
     let databasep  = format!("-D{}",database);
-    let sqlp = format!("-e'{sql}'");
-    println!("----- {sql} -----");
+    let sqlp = format!("-e'{}'", sql.replace('\'', r"'\''"));
+    eprintln!("----- {sql} -----");
     let passwd_set = format!("export MYSQL_PWD={passwd}");
-    let passwdp = "";
     let mysql_cmd = format!("mysql -A {urlp} {userp} {databasep} {sqlp}");
-    let passwd_unset = format!("unset MYSQL_PWD");
+    let passwd_unset = "unset MYSQL_PWD";
     let cmd = format!("{passwd_set};{mysql_cmd};{passwd_unset};");
     let status = Command::new("sh")
         .arg("-c")
@@ -244,7 +429,7 @@ pub fn exe_sql(env_rw: &DatabaseEnv, sql:&str)->ExitStatus {
     //     .status()
     //     .expect("failed to execute process");
 
-    println!("process finished with: {status}");
+    eprintln!("process finished with: {status}");
     status
 }
 
@@ -258,5 +443,11 @@ mod tests {
         let result = 4;
         assert_eq!(result, 4);
     }
+
+    #[test]
+    fn batch_field_unescape() {
+        assert_eq!(batch_field(r"a\tb\nc\\d"), "a\tb\nc\\d");
+        assert_eq!(batch_field("plain"), "plain");
+    }
 }
 
@@ -1,5 +1,10 @@
 use std::{fmt::Debug, process::ExitStatus};
+use super::backup::BackupEnv;
 use super::cfg;
+use super::confirm::ConfirmEnv;
+use super::dump::DumpEnv;
+use super::pipeline::Pipelines;
+use super::sqlload::LoadEnv;
 
 pub fn load_panel_env(cfg:Option<String>)->PanelEnv {
     load_env::<PanelEnv>(cfg)
@@ -8,14 +13,14 @@ pub fn load_panel_env(cfg:Option<String>)->PanelEnv {
 pub fn load_env<T>(cfg:Option<String>) -> T
 where T: serde::de::DeserializeOwned+ Debug,
 {
-    println!("-------- {cfg:?} --------");
+    eprintln!("-------- {cfg:?} --------");
     let cfg = cfg::get_cfg(cfg);
-    println!("Loading configuration from: {:?}", cfg);
+    eprintln!("Loading configuration from: {:?}", cfg);
     let content = std::fs::read_to_string(&cfg)
         .expect("Failed to read configuration file");
     let cfg = toml::from_str::<T>(&content)
         .expect("Failed to parse configuration file");
-    println!("Loaded configuration.");
+    eprintln!("Loaded configuration.");
     cfg
 }
 
@@ -31,6 +36,18 @@ pub struct PanelEnv {
     pub years: String,
     pub months: String,
     pub basedir: String,
+    #[serde(default)]
+    pub trash: TrashEnv,
+    #[serde(default)]
+    pub confirm: ConfirmEnv,
+    #[serde(default)]
+    pub backup: BackupEnv,
+    #[serde(default)]
+    pub pipelines: Pipelines,
+    #[serde(default)]
+    pub dump: DumpEnv,
+    #[serde(default)]
+    pub load: LoadEnv,
 }
 
 impl PanelEnv {
@@ -52,7 +69,7 @@ impl PanelEnv {
         }
     }
 
-    pub fn table_rule(&self)->TableRule {
+    pub fn table_rule(&self)->TableRule<'_> {
         let names = self.names.iter().map(
             |e|e.as_str()).collect();
         let years = self.years.split_whitespace().collect();
@@ -66,12 +83,42 @@ impl PanelEnv {
 
 }
 
+// [trash]
+// enabled = true
+// database = "trash"
+#[derive(Debug, serde::Deserialize)]
+pub struct TrashEnv {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "TrashEnv::default_database")]
+    pub database: String,
+}
+
+impl TrashEnv {
+    fn default_database()->String {
+        "trash".into()
+    }
+}
+
+impl Default for TrashEnv {
+    fn default()->Self {
+        Self {
+            enabled: false,
+            database: Self::default_database(),
+        }
+    }
+}
+
+#[allow(dead_code)]
 #[derive(Debug, serde::Deserialize)]
 pub struct ZipEnv {
     pub basedir: String,
     pub years: String,
 }
 
+pub type TableHandle<'h> = &'h dyn Fn(&str, &str, usize);
+pub type TableHandleMut<'h> = &'h mut dyn FnMut(&str, &str);
+
 pub struct TableRule<'a> {
     pub names: Vec<&'a str>,
     pub years: Vec<&'a str>,
@@ -87,7 +134,59 @@ impl TableRule<'_> {
         }
     }
 
-    pub fn for_each_tables(&self, handles: &[&dyn Fn(&str, &str, usize)]) {
+    pub fn tables(&self)->Vec<String> {
+        let mut tables = Vec::new();
+        for name in &self.names {
+            for year in &self.years {
+                for month in &self.months {
+                    tables.push(format!("{}{}{}", name, year, month));
+                }
+            }
+        }
+        tables
+    }
+
+    // (name, year, month) of every table
+    pub fn parts(&self)->Vec<(&str, &str, &str)> {
+        let mut parts = Vec::new();
+        for name in &self.names {
+            for year in &self.years {
+                for month in &self.months {
+                    parts.push((*name, *year, *month));
+                }
+            }
+        }
+        parts
+    }
+
+    // the year of a rule table, the table may carry a postfix
+    pub fn year_of(&self, table:&str)->Option<&str> {
+        for name in &self.names {
+            for year in &self.years {
+                for month in &self.months {
+                    if table.starts_with(&format!("{}{}{}", name, year, month)) {
+                        return Some(year);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn name_of(&self, table:&str)->Option<&str> {
+        for name in &self.names {
+            for year in &self.years {
+                for month in &self.months {
+                    if table.starts_with(&format!("{}{}{}", name, year, month)) {
+                        return Some(name);
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    pub fn for_each_tables(&self, handles: &[TableHandle]) {
         let mut i = 0;
         for name in &self.names {
             for year in &self.years {
@@ -102,7 +201,7 @@ impl TableRule<'_> {
         }
     }
 
-    pub fn for_each_tables_mut(&self, handles: &mut[&mut dyn FnMut(&str, &str)]) {
+    pub fn for_each_tables_mut(&self, handles: &mut[TableHandleMut]) {
         for name in &self.names {
             for year in &self.years {
                 for month in &self.months {
@@ -116,7 +215,7 @@ impl TableRule<'_> {
     }
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct DatabaseEnv {
     pub(crate) url: String,
     pub(crate) user: String,
@@ -124,6 +223,12 @@ pub struct DatabaseEnv {
     pub database: String,
 }
 
+impl Default for DatabaseEnv {
+    fn default()->Self {
+        Self::new()
+    }
+}
+
 impl DatabaseEnv {
     pub fn new()->Self {
         Self {
@@ -0,0 +1,189 @@
+use std::collections::BTreeMap;
+use std::process::Command;
+
+use super::{add_postfix, create_empty, drop_sql, dump_out_with, exe_sql, zip};
+use super::backup;
+use super::catalog;
+use super::confirm::Approval;
+use super::panelenv::PanelEnv;
+use super::trash;
+
+// [pipelines.archive]
+// steps = ["take", "dumpout", "zip", "verify", "drop"]
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct Pipeline {
+    pub steps: Vec<String>,
+}
+
+pub type Pipelines = BTreeMap<String, Pipeline>;
+
+// take moves {table} to {table}{postfix}, the other steps act on {table}{postfix}
+pub const STEPS: &[&str] = &["take", "dumpout", "zip", "verify", "drop"];
+
+struct Unit {
+    name: String,
+    year: String,
+    month: String,
+    table: String,
+    // the step which stopped this table, and why
+    failed: Option<(String, String)>,
+}
+
+impl Unit {
+    fn alive(&self)->bool {
+        self.failed.is_none()
+    }
+
+    fn fail(&mut self, step:&str, reason:String) {
+        eprintln!("----- {} stopped at {step}: {reason} -----", self.table);
+        self.failed = Some((step.to_string(), reason));
+    }
+}
+
+pub struct Outcome {
+    pub table: String,
+    pub failed: Option<(String, String)>,
+}
+
+pub fn check(pipeline:&Pipeline)->Result<(), String> {
+    match pipeline.steps.iter().find(|step| !STEPS.contains(&step.as_str())) {
+        Some(step) => Err(format!("unknown step {step}, expected one of {}", STEPS.join(" "))),
+        None => Ok(()),
+    }
+}
+
+// every step runs over all tables still alive, a failure stops only that table
+pub fn run(env:&PanelEnv, pipeline:&Pipeline, postfix:&str, approval:&Approval)->Vec<Outcome> {
+    let env_rw = env.to_rw_dbenv();
+    let rule = env.table_rule();
+    let basedir = &env.basedir;
+    let mut units = Vec::new();
+    for name in &rule.names {
+        for year in &rule.years {
+            for month in &rule.months {
+                units.push(Unit {
+                    name: name.to_string(),
+                    year: year.to_string(),
+                    month: month.to_string(),
+                    table: format!("{name}{year}{month}"),
+                    failed: None,
+                });
+            }
+        }
+    }
+
+    for step in &pipeline.steps {
+        println!("========== {step} ==========");
+        match step.as_str() {
+            "take" => {
+                for unit in units.iter_mut().filter(|u| u.alive()) {
+                    let table = &unit.table;
+                    let taken = format!("{table}{postfix}");
+                    if env.confirm.is_protected(table) {
+                        unit.fail(step, "protected".into());
+                    } else if !backup::ensure(&env_rw, &env.backup, table, &format!("{basedir}/{}", unit.year), &env.dump.options_for(&unit.name)) {
+                        unit.fail(step, "no verified dump".into());
+                    } else if !add_postfix(&env_rw, table, postfix).success() {
+                        unit.fail(step, format!("rename to {taken} failed"));
+                    } else if !create_empty(&env_rw, &taken, table).success() {
+                        unit.fail(step, format!("create empty {table} failed"));
+                    }
+                }
+            }
+            "dumpout" => {
+                for unit in units.iter_mut().filter(|u| u.alive()) {
+                    let table = format!("{}{postfix}", unit.table);
+                    let outdir = format!("{basedir}/{}", unit.year);
+                    let options = env.dump.options_for(&unit.name);
+                    if !dump_out_with(&env_rw, &table, &outdir, &options).success() {
+                        unit.fail(step, format!("dump of {table} failed"));
+                    } else if options.is_complete()
+                        && let Err(err) = catalog::record_dump(basedir, &unit.name, &unit.year, &unit.month, &table) {
+                        eprintln!("----- failed to catalog {table}: {err} -----");
+                    }
+                }
+            }
+            "zip" => {
+                // one archive per name and year
+                let mut groups = units.iter()
+                    .filter(|u| u.alive())
+                    .map(|u| (u.name.clone(), u.year.clone()))
+                    .collect::<Vec<_>>();
+                groups.dedup();
+                for (name, year) in groups {
+                    if zip(basedir, &year, &name).success() {
+                        if let Err(err) = catalog::record_zip(basedir, &year, &name) {
+                            eprintln!("----- failed to catalog {name}{year}.zip: {err} -----");
+                        }
+                        continue;
+                    }
+                    for unit in units.iter_mut().filter(|u| u.alive() && u.name == name && u.year == year) {
+                        unit.fail(step, format!("zip of {name}{year} failed"));
+                    }
+                }
+            }
+            "verify" => {
+                for unit in units.iter_mut().filter(|u| u.alive()) {
+                    let table = format!("{}{postfix}", unit.table);
+                    let outdir = format!("{basedir}/{}", unit.year);
+                    if let Err(reason) = backup::verify(&env_rw, &table, &outdir) {
+                        unit.fail(step, reason);
+                        continue;
+                    }
+                    let zipfile = format!("{outdir}/{}{}.zip", unit.name, unit.year);
+                    if std::path::Path::new(&zipfile).exists() && !zip_has(&zipfile, &table) {
+                        unit.fail(step, format!("{table}.sql is not in {zipfile}"));
+                    }
+                }
+            }
+            "drop" => {
+                let mut tables = Vec::new();
+                for unit in units.iter_mut().filter(|u| u.alive()) {
+                    let table = format!("{}{postfix}", unit.table);
+                    if backup::ensure(&env_rw, &env.backup, &table, &format!("{basedir}/{}", unit.year), &env.dump.options_for(&unit.name)) {
+                        tables.push(table);
+                    } else {
+                        unit.fail(step, "no verified dump".into());
+                    }
+                }
+                // confirmed once for the batch, then dropped table by table
+                let action = if env.trash.enabled { "TRASH" } else { "DROP" };
+                if !env.confirm.confirm_batch(&env_rw.database, action, &tables, approval) {
+                    for unit in units.iter_mut().filter(|u| u.alive()) {
+                        unit.fail(step, "not confirmed".into());
+                    }
+                    continue;
+                }
+                for unit in units.iter_mut().filter(|u| u.alive()) {
+                    let table = format!("{}{postfix}", unit.table);
+                    let status = if env.trash.enabled {
+                        trash::soft_drop(&env_rw, &env.trash, &table)
+                    } else {
+                        exe_sql(&env_rw, &drop_sql(&table))
+                    };
+                    if !status.success() {
+                        unit.fail(step, format!("drop of {table} failed"));
+                    }
+                }
+            }
+            _ => unreachable!("checked by pipeline::check"),
+        }
+    }
+
+    units.into_iter()
+        .map(|u| Outcome {
+            table: u.table,
+            failed: u.failed,
+        })
+        .collect()
+}
+
+fn zip_has(zipfile:&str, table:&str)->bool {
+    Command::new("unzip")
+        .arg("-l")
+        .arg(zipfile)
+        .arg(format!("*/{table}.sql"))
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false)
+}
@@ -0,0 +1,188 @@
+use std::process::ExitStatus;
+
+use super::{count_rows, create_empty_sql, drop_sql, exe_sql, exit_status, rename_sql, table_exists};
+use super::clock;
+use super::panelenv::{DatabaseEnv, TableRule, TrashEnv};
+use super::trash;
+
+// what a batch command will do, written by `migrate plan` and replayed by `migrate apply`
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Plan {
+    pub command: String,
+    pub database: String,
+    pub postfix: String,
+    pub created_at: String,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct Step {
+    pub table: String,
+    // the live state the sql was planned against
+    pub state: Vec<TableState>,
+    pub sql: Vec<String>,
+    pub expect: String,
+}
+
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct TableState {
+    pub table: String,
+    pub exists: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rows: Option<u64>,
+}
+
+impl TableState {
+    pub fn probe(dbe:&DatabaseEnv, table:&str)->Self {
+        let exists = table_exists(dbe, table);
+        let rows = if exists { count_rows(dbe, table) } else { None };
+        Self {
+            table: table.to_string(),
+            exists,
+            rows,
+        }
+    }
+}
+
+impl Plan {
+    fn new(dbe:&DatabaseEnv, command:&str, postfix:&str)->Self {
+        Self {
+            command: command.to_string(),
+            database: dbe.database.clone(),
+            postfix: postfix.to_string(),
+            created_at: clock::now_stamp(),
+            steps: Vec::new(),
+        }
+    }
+
+    pub fn take(dbe:&DatabaseEnv, rule:&TableRule, postfix:&str)->Self {
+        let mut plan = Self::new(dbe, "take", postfix);
+        for table in rule.tables() {
+            let taken = format!("{table}{postfix}");
+            let src = TableState::probe(dbe, &table);
+            let dst = TableState::probe(dbe, &taken);
+            let (sql, expect) = match (&src, dst.exists) {
+                (TableState { exists: false, .. }, _) => (vec![], format!("skipped, {table} does not exist")),
+                (_, true) => (vec![], format!("skipped, {taken} already exists")),
+                (TableState { rows, .. }, _) => {
+                    let rows = rows.map_or("the".to_string(), |rows| rows.to_string());
+                    let sql = vec![rename_sql(&table, &taken), create_empty_sql(&taken, &table)];
+                    (sql, format!("{taken} holds {rows} rows, {table} is empty"))
+                }
+            };
+            plan.steps.push(Step {
+                sql,
+                table,
+                state: vec![src, dst],
+                expect,
+            });
+        }
+        plan
+    }
+
+    pub fn batch_drop(dbe:&DatabaseEnv, rule:&TableRule, postfix:&str, trash:&TrashEnv)->Self {
+        let mut plan = Self::new(dbe, "batch-drop", postfix);
+        for table in rule.tables() {
+            let table = format!("{table}{postfix}");
+            let state = TableState::probe(dbe, &table);
+            let (sql, expect) = if !state.exists {
+                (vec![], format!("nothing, {table} does not exist"))
+            } else if trash.enabled {
+                let sql = trash::soft_drop_sql(dbe, trash, &table).into_iter().collect();
+                (sql, format!("{table} moved into trash {}", trash.database))
+            } else {
+                (vec![drop_sql(&table)], format!("{table} dropped"))
+            };
+            plan.steps.push(Step {
+                table,
+                state: vec![state],
+                sql,
+                expect,
+            });
+        }
+        plan
+    }
+
+    // tables the steps will act on
+    pub fn tables(&self)->Vec<String> {
+        self.steps.iter()
+            .filter(|step| !step.sql.is_empty())
+            .map(|step| step.table.clone())
+            .collect()
+    }
+
+    // every difference between the recorded and the live state
+    pub fn drift(&self, dbe:&DatabaseEnv)->Vec<String> {
+        let mut drift = Vec::new();
+        if self.database != dbe.database {
+            drift.push(format!("plan is for database {}, not {}", self.database, dbe.database));
+            return drift;
+        }
+        for step in &self.steps {
+            for planned in &step.state {
+                let live = TableState::probe(dbe, &planned.table);
+                if live != *planned {
+                    drift.push(format!("{}: planned exists={} rows={:?}, live exists={} rows={:?}",
+                        planned.table, planned.exists, planned.rows, live.exists, live.rows));
+                }
+            }
+        }
+        drift
+    }
+
+    pub fn apply(&self, dbe:&DatabaseEnv)->ExitStatus {
+        for step in &self.steps {
+            for sql in &step.sql {
+                let status = exe_sql(dbe, sql);
+                if !status.success() {
+                    eprintln!("----- {} failed, expected: {} -----", step.table, step.expect);
+                    return status;
+                }
+            }
+            println!("----- {}: {} -----", step.table, step.expect);
+        }
+        exit_status(0)
+    }
+
+    pub fn save(&self, path:&str)->std::io::Result<()> {
+        let content = toml::to_string(self).expect("serialize plan");
+        std::fs::write(path, content)
+    }
+
+    pub fn load(path:&str)->Self {
+        let content = std::fs::read_to_string(path)
+            .expect("Failed to read plan file");
+        toml::from_str(&content)
+            .expect("Failed to parse plan file")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn plan_round_trip() {
+        let plan = Plan {
+            command: "take".into(),
+            database: "db".into(),
+            postfix: "_bak".into(),
+            created_at: "20261019083000".into(),
+            steps: vec![Step {
+                table: "sales1701".into(),
+                state: vec![
+                    TableState { table: "sales1701".into(), exists: true, rows: Some(3) },
+                    TableState { table: "sales1701_bak".into(), exists: false, rows: None },
+                ],
+                sql: vec![rename_sql("sales1701", "sales1701_bak")],
+                expect: "sales1701_bak holds 3 rows, sales1701 is empty".into(),
+            }],
+        };
+        let content = toml::to_string(&plan).unwrap();
+        let back: Plan = toml::from_str(&content).unwrap();
+        assert_eq!(back.steps[0].state, plan.steps[0].state);
+        assert_eq!(back.steps[0].sql, plan.steps[0].sql);
+        assert_eq!(back.tables(), vec!["sales1701".to_string()]);
+    }
+}
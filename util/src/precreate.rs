@@ -0,0 +1,123 @@
+use super::{create_empty, exe_sql_with_output, table_exists};
+use super::clock;
+use super::panelenv::DatabaseEnv;
+use super::report::Row;
+
+#[derive(Debug, serde::Serialize)]
+pub struct PrecreateReport {
+    pub name: String,
+    pub table: String,
+    pub template: String,
+    pub status: String,
+}
+
+impl Row for PrecreateReport {
+    const COLUMNS: &'static [&'static str] = &["name", "table", "template", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.name.clone(),
+            self.table.clone(),
+            self.template.clone(),
+            self.status.clone(),
+        ]
+    }
+}
+
+// the yymm of the `ahead` months following (year, month)
+pub fn next_months(year:i64, month:i64, ahead:usize)->Vec<String> {
+    (1..=ahead as i64)
+        .map(|i| {
+            let m = month - 1 + i;
+            let (y, m) = (year + m / 12, m % 12 + 1);
+            format!("{:02}{m:02}", y % 100)
+        })
+        .collect()
+}
+
+// months since year 0 of a yymm, the two digit year taken in the century nearest to year
+fn month_index(yymm:&str, year:i64)->Option<i64> {
+    if yymm.len() != 4 || !yymm.bytes().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (yy, m) = (yymm[..2].parse::<i64>().ok()?, yymm[2..].parse::<i64>().ok()?);
+    if !(1..=12).contains(&m) {
+        return None;
+    }
+    let mut y = year - year % 100 + yy;
+    if y > year + 50 {
+        y -= 100;
+    } else if y < year - 50 {
+        y += 100;
+    }
+    Some(y * 12 + m - 1)
+}
+
+// the newest of the {name}yymm tables which is not after (year, month)
+fn newest_month(tables:&[String], name:&str, now:(i64, i64))->Option<String> {
+    let current = now.0 * 12 + now.1 - 1;
+    tables.iter()
+        .filter_map(|t| Some((month_index(t.strip_prefix(name)?, now.0)?, t)))
+        .filter(|(index, _)| *index <= current)
+        .max_by_key(|(index, _)| *index)
+        .map(|(_, t)| t.clone())
+}
+
+// the newest {name}yymm table of the database up to the current month, tables
+// pre-created for later months are no template
+pub fn latest_month(dbe:&DatabaseEnv, name:&str)->Option<String> {
+    let sql = format!("SELECT TABLE_NAME FROM information_schema.TABLES \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME REGEXP '^{name}[0-9]{{4}}$'");
+    let output = exe_sql_with_output(dbe, &sql);
+    assert!(output.status.success());
+    let tables = String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect::<Vec<_>>();
+    newest_month(&tables, name, clock::year_month(clock::now_secs()))
+}
+
+// the tables of the months after the current one, created like the template
+// or like the newest month of their name
+pub fn precreate(dbe:&DatabaseEnv, names:&[&str], ahead:usize, template:Option<&str>)->Vec<PrecreateReport> {
+    let (year, month) = clock::year_month(clock::now_secs());
+    let months = next_months(year, month, ahead);
+    let mut reports = Vec::new();
+    for name in names {
+        let template = template.map(String::from).or_else(|| latest_month(dbe, name));
+        for yymm in &months {
+            let table = format!("{name}{yymm}");
+            let status = match &template {
+                _ if table_exists(dbe, &table) => "exists",
+                None => "no template",
+                Some(template) if create_empty(dbe, template, &table).success() => "created",
+                Some(_) => "failed",
+            };
+            reports.push(PrecreateReport {
+                name: name.to_string(),
+                table,
+                template: template.clone().unwrap_or_default(),
+                status: status.into(),
+            });
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn months_after() {
+        assert_eq!(next_months(2017, 10, 4), vec!["1711", "1712", "1801", "1802"]);
+        assert_eq!(next_months(2099, 12, 1), vec!["0001"]);
+        assert!(next_months(2017, 1, 0).is_empty());
+    }
+
+    #[test]
+    fn newest_month_up_to_now() {
+        let tables = ["sales9911", "sales9912", "sales0001", "sales0003", "sales0013"]
+            .map(String::from);
+        assert_eq!(newest_month(&tables, "sales", (2000, 2)).as_deref(), Some("sales0001"));
+        assert_eq!(newest_month(&tables, "sales", (2000, 3)).as_deref(), Some("sales0003"));
+        assert_eq!(newest_month(&tables, "sales", (1999, 12)).as_deref(), Some("sales9912"));
+        assert_eq!(newest_month(&tables, "sales", (1999, 10)), None);
+    }
+}
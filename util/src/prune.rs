@@ -0,0 +1,227 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::time::{Duration, Instant};
+
+use super::{batch_field, exe_sql_with_output, table_exists};
+use super::clock;
+use super::panelenv::DatabaseEnv;
+use super::report::{opt, Row};
+use super::schema::{ident_list, primary_key, ColumnInfo};
+use super::session::{Outcome, Session};
+use super::sqldump::{dumped_columns, select_sql, write_row, Inserts, INSERT_BYTES};
+
+pub struct PruneOptions<'a> {
+    pub predicate: &'a str,
+    pub chunk: usize,
+    pub sleep: Duration,
+    // no new chunk is started once this has passed
+    pub max_runtime: Option<Duration>,
+    // each chunk is written here before it is deleted, as {dumpdir}/{table}-prune-{stamp}.sql
+    pub dumpdir: Option<&'a str>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct PruneReport {
+    pub table: String,
+    pub matched: Option<u64>,
+    pub deleted: u64,
+    pub status: String,
+}
+
+impl Row for PruneReport {
+    const COLUMNS: &'static [&'static str] = &["table", "matched", "deleted", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.table.clone(),
+            opt(self.matched),
+            self.deleted.to_string(),
+            self.status.clone(),
+        ]
+    }
+}
+
+fn single_number(dbe:&DatabaseEnv, sql:&str)->Option<u64> {
+    let output = exe_sql_with_output(dbe, sql);
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .last()
+        .and_then(|n| n.trim().parse().ok())
+}
+
+fn run(session:&mut Session, sql:&str)->Result<u64, String> {
+    match session.execute(sql, ";")? {
+        Outcome { failed: false, affected, .. } => Ok(affected.unwrap_or(0)),
+        _ => Err(format!("{sql} failed")),
+    }
+}
+
+// where the rows of each chunk are written before the chunk is deleted
+struct ChunkDump<'w, W:Write> {
+    inserts: Inserts<'w, W>,
+    // the next chunk of matching rows as VALUES tuple fields, locked until the chunk commits
+    select: String,
+    // the positions of the key columns in the tuple, and whether each is a number
+    key: Vec<(usize, bool)>,
+}
+
+impl<W:Write> ChunkDump<'_, W> {
+    // the key of a tuple as a literal usable in (key) <= (...), numbers unquoted
+    fn key_of(&self, line:&str)->String {
+        let fields = line.split('\t').map(batch_field).collect::<Vec<_>>();
+        self.key.iter()
+            .map(|&(i, numeric)| {
+                let field = fields.get(i).map(String::as_str).unwrap_or("NULL");
+                if numeric { field.trim_matches('\'').to_string() } else { field.to_string() }
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+// one chunk in one transaction; with a dump its rows are locked, written and flushed first, and
+// only the matching rows up to the last key written are deleted, committed when the delete hit
+// exactly the rows written. Returns the rows deleted, a rolled back chunk stays in the dump
+// while its rows stay in the table
+fn prune_chunk(session:&mut Session, table:&str, predicate:&str, order:&str, chunk:usize, dump:Option<&mut ChunkDump<impl Write>>)->Result<u64, String> {
+    run(session, "START TRANSACTION")?;
+    let delete = |session:&mut Session| {
+        let Some(dump) = dump else {
+            return run(session, &format!("DELETE FROM {table} WHERE {predicate} ORDER BY {order} LIMIT {chunk}"));
+        };
+        let mut rows = 0;
+        let mut last = None;
+        let read = session.each_row(&dump.select, |line| {
+            rows += 1;
+            last = Some(line.to_string());
+            write_row(line, &mut dump.inserts)
+        })?;
+        dump.inserts.flush().map_err(|e| format!("writing the dump of {table}: {e}"))?;
+        if read.failed {
+            return Err(format!("reading {table} failed"));
+        }
+        let Some(last) = last else {
+            return Ok(0);
+        };
+        let deleted = run(session, &format!("DELETE FROM {table} WHERE ({predicate}) AND ({order}) <= ({})", dump.key_of(&last)))?;
+        if deleted != rows {
+            return Err(format!("{rows} rows dumped but {deleted} deleted"));
+        }
+        Ok(deleted)
+    };
+    match delete(session) {
+        Ok(deleted) => run(session, "COMMIT").map(|_| deleted),
+        Err(err) => {
+            run(session, "ROLLBACK")?;
+            Err(format!("{err}, rolled back"))
+        }
+    }
+}
+
+fn open_dump(dumpdir:&str, table:&str)->Result<BufWriter<File>, String> {
+    let outfile = format!("{dumpdir}/{table}-prune-{}.sql", clock::now_stamp());
+    std::fs::create_dir_all(dumpdir).map_err(|e| format!("{dumpdir}: {e}"))?;
+    let mut out = BufWriter::new(File::create(&outfile).map_err(|e| format!("{outfile}: {e}"))?);
+    writeln!(out, "SET NAMES utf8mb4;\nSET TIME_ZONE='+00:00';").map_err(|e| format!("{outfile}: {e}"))?;
+    Ok(out)
+}
+
+fn chunk_dump<'w, W:Write>(dbe:&DatabaseEnv, out:&'w mut W, table:&str, predicate:&str, key:&[String], chunk:usize)->ChunkDump<'w, W> {
+    let cols = dumped_columns(dbe, table);
+    let numeric = |c:&ColumnInfo| c.is_integer() || c.data_type == "decimal";
+    let key_at = key.iter()
+        .filter_map(|k| cols.iter().position(|c| &c.name == k).map(|i| (i, numeric(&cols[i]))))
+        .collect();
+    ChunkDump {
+        select: format!("{} LIMIT {chunk} FOR UPDATE", select_sql(table, &cols, key, Some(&format!("({predicate})")))),
+        inserts: Inserts::new(out, table, &cols, INSERT_BYTES),
+        key: key_at,
+    }
+}
+
+// rows matching the predicate are deleted chunk by chunk in primary key order, each chunk in its
+// own transaction together with writing it to the dump
+pub fn prune(dbe:&DatabaseEnv, tables:&[String], options:&PruneOptions)->Vec<PruneReport> {
+    let started = Instant::now();
+    let predicate = options.predicate;
+    let mut reports = Vec::new();
+    let mut timed_out = false;
+    // TIMESTAMP values are read in the zone the dump sets
+    let session = Session::open(dbe)
+        .and_then(|mut session| run(&mut session, "SET TIME_ZONE='+00:00'").map(|_| session));
+    let mut session = match session {
+        Ok(session) => session,
+        Err(err) => {
+            eprintln!("----- {err} -----");
+            return tables.iter()
+                .map(|table| PruneReport {
+                    table: table.clone(),
+                    matched: None,
+                    deleted: 0,
+                    status: "no connection".into(),
+                })
+                .collect();
+        }
+    };
+    for table in tables {
+        let mut report = PruneReport {
+            table: table.clone(),
+            matched: None,
+            deleted: 0,
+            status: String::new(),
+        };
+        if timed_out {
+            report.status = "not started".into();
+            reports.push(report);
+            continue;
+        }
+        if !table_exists(dbe, table) {
+            report.status = "missing".into();
+            reports.push(report);
+            continue;
+        }
+        let key = primary_key(dbe, table);
+        if key.is_empty() {
+            report.status = "no primary key".into();
+            reports.push(report);
+            continue;
+        }
+        report.matched = single_number(dbe, &format!("SELECT COUNT(*) FROM {table} WHERE {predicate}"));
+        let mut out = match options.dumpdir.map(|dumpdir| open_dump(dumpdir, table)).transpose() {
+            Ok(out) => out,
+            Err(err) => {
+                eprintln!("----- {err} -----");
+                report.status = "dump failed".into();
+                reports.push(report);
+                continue;
+            }
+        };
+        let mut dump = out.as_mut().map(|out| chunk_dump(dbe, out, table, predicate, &key, options.chunk));
+
+        let order = ident_list(&key);
+        report.status = loop {
+            if options.max_runtime.is_some_and(|max| started.elapsed() >= max) {
+                timed_out = true;
+                break "max runtime reached";
+            }
+            let deleted = match prune_chunk(&mut session, table, predicate, &order, options.chunk, dump.as_mut()) {
+                Ok(deleted) => deleted,
+                Err(err) => {
+                    eprintln!("----- {table}: {err} -----");
+                    break "delete failed";
+                }
+            };
+            report.deleted += deleted;
+            if deleted == 0 {
+                break "pruned";
+            }
+            eprintln!("----- {table}: {} of {} rows deleted -----", report.deleted, opt(report.matched));
+            std::thread::sleep(options.sleep);
+        }.into();
+        reports.push(report);
+    }
+    session.close();
+    reports
+}
@@ -0,0 +1,190 @@
+use std::io::Write;
+
+use super::{count_rows, exe_sql_with_output, is_empty};
+use super::panelenv::DatabaseEnv;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Table,
+    Json,
+    Csv,
+}
+
+impl OutputFormat {
+    pub fn parse(s:&str)->Option<Self> {
+        match s {
+            "table" => Some(Self::Table),
+            "json" => Some(Self::Json),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+// one line of `migrate count` or `migrate empty`, what was not measured stays None
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct TableReport {
+    pub name: String,
+    pub year: String,
+    pub month: String,
+    pub table: String,
+    pub exists: bool,
+    pub rows: Option<u64>,
+    pub empty: Option<bool>,
+    pub bytes: Option<u64>,
+}
+
+impl TableReport {
+    // exists and bytes come from information_schema, rows and empty only when asked
+    pub fn probe(dbe:&DatabaseEnv, parts:(&str, &str, &str), table:&str, rows:bool, empty:bool)->Self {
+        let (name, year, month) = parts;
+        let bytes = table_bytes(dbe, table);
+        let exists = bytes.is_some();
+        let rows = if exists && rows { count_rows(dbe, table) } else { None };
+        let empty = match (exists, rows) {
+            (false, _) => None,
+            (true, Some(rows)) => Some(rows == 0),
+            (true, None) if empty => Some(is_empty(dbe, table)),
+            (true, None) => None,
+        };
+        Self {
+            name: name.into(),
+            year: year.into(),
+            month: month.into(),
+            table: table.into(),
+            exists,
+            rows,
+            empty,
+            bytes,
+        }
+    }
+}
+
+// a record printable as json, csv or an aligned table
+pub trait Row: serde::Serialize {
+    const COLUMNS: &'static [&'static str];
+    fn cells(&self)->Vec<String>;
+}
+
+pub(crate) fn opt<T:ToString>(v:Option<T>)->String {
+    v.map(|v| v.to_string()).unwrap_or_default()
+}
+
+impl Row for TableReport {
+    const COLUMNS: &'static [&'static str] = &["name", "year", "month", "table", "exists", "rows", "empty", "bytes"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.name.clone(),
+            self.year.clone(),
+            self.month.clone(),
+            self.table.clone(),
+            self.exists.to_string(),
+            opt(self.rows),
+            opt(self.empty),
+            opt(self.bytes),
+        ]
+    }
+}
+
+pub fn write_reports<T:Row>(format:OutputFormat, reports:&[T], out:&mut impl Write)->std::io::Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer_pretty(&mut *out, reports)?;
+            writeln!(out)
+        }
+        OutputFormat::Csv => {
+            writeln!(out, "{}", T::COLUMNS.join(","))?;
+            for r in reports {
+                let line = r.cells().iter()
+                    .map(|c| csv_field(c))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                writeln!(out, "{line}")?;
+            }
+            Ok(())
+        }
+        OutputFormat::Table => {
+            let header = T::COLUMNS.iter().map(|c| c.to_string()).collect::<Vec<_>>();
+            let rows = reports.iter().map(Row::cells).collect::<Vec<_>>();
+            let mut widths = header.iter().map(String::len).collect::<Vec<_>>();
+            for row in &rows {
+                for (w, c) in widths.iter_mut().zip(row) {
+                    *w = (*w).max(c.len());
+                }
+            }
+            let line = |cols:&[String]| {
+                cols.iter().zip(&widths)
+                    .map(|(c, w)| format!("{c:<w$}"))
+                    .collect::<Vec<_>>()
+                    .join("  ")
+            };
+            writeln!(out, "{}", line(&header).trim_end())?;
+            for row in &rows {
+                writeln!(out, "{}", line(row).trim_end())?;
+            }
+            Ok(())
+        }
+    }
+}
+
+pub fn csv_field(s:&str)->String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+// data plus index length, None when the table does not exist
+pub fn table_bytes(dbe:&DatabaseEnv, table:&str)->Option<u64> {
+    let sql = format!("SELECT DATA_LENGTH+INDEX_LENGTH FROM information_schema.TABLES WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}'");
+    let output = exe_sql_with_output(dbe, &sql);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let line = stdout.lines().next()?;
+    Some(line.trim().parse().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample()->Vec<TableReport> {
+        vec![TableReport {
+            name: "sales".into(),
+            year: "17".into(),
+            month: "01".into(),
+            table: "sales1701".into(),
+            exists: true,
+            rows: Some(42),
+            empty: Some(false),
+            bytes: Some(16384),
+        }, TableReport {
+            name: "sales".into(),
+            year: "17".into(),
+            month: "02".into(),
+            table: "sales1702".into(),
+            ..Default::default()
+        }]
+    }
+
+    #[test]
+    fn csv_output() {
+        let mut out = Vec::new();
+        write_reports(OutputFormat::Csv, &sample(), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!(out, "name,year,month,table,exists,rows,empty,bytes\n\
+            sales,17,01,sales1701,true,42,false,16384\n\
+            sales,17,02,sales1702,false,,,\n");
+        assert_eq!(csv_field("a,\"b\""), "\"a,\"\"b\"\"\"");
+    }
+
+    #[test]
+    fn json_output() {
+        let mut out = Vec::new();
+        write_reports(OutputFormat::Json, &sample(), &mut out).unwrap();
+        let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+        assert_eq!(value[0]["rows"], 42);
+        assert!(value[1]["rows"].is_null());
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use super::{batch_field, exe_sql_with_output};
+use super::panelenv::DatabaseEnv;
+use super::report::csv_field;
+use super::schema::{column_info, columns, ident_list, primary_key, quote_ident};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DiffFormat {
+    Sql,
+    Csv,
+}
+
+impl DiffFormat {
+    pub fn parse(s:&str)->Option<Self> {
+        match s {
+            "sql" => Some(Self::Sql),
+            "csv" => Some(Self::Csv),
+            _ => None,
+        }
+    }
+}
+
+// rows of left not in right, rows of right not in left, and rows whose values differ
+#[derive(Debug, Default)]
+pub struct RowDiff {
+    pub columns: Vec<String>,
+    pub key: Vec<String>,
+    pub missing: Vec<String>,
+    pub extra: Vec<String>,
+    pub changed: Vec<String>,
+}
+
+impl RowDiff {
+    pub fn is_empty(&self)->bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.changed.is_empty()
+    }
+}
+
+struct Tables<'a> {
+    dbe: &'a DatabaseEnv,
+    // CONCAT_WS of the key columns, QUOTE()d unless numeric, a literal usable in (key) = (...)
+    key_lit: String,
+    key_cols: String,
+    row_hash: String,
+}
+
+impl Tables<'_> {
+    fn query(&self, sql:&str)->Vec<Vec<String>> {
+        let output = exe_sql_with_output(self.dbe, sql);
+        assert!(output.status.success(), "{sql}");
+        String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split('\t').map(batch_field).collect())
+            .collect()
+    }
+
+    // the last key of the chunk of table following `after`
+    fn boundary(&self, table:&str, after:Option<&str>, chunk:usize)->Option<String> {
+        let filter = after.map(|lit| format!("WHERE ({}) > ({lit})", self.key_cols)).unwrap_or_default();
+        let sql = format!("SELECT {} FROM {table} {filter} ORDER BY {} LIMIT 1 OFFSET {}",
+            self.key_lit, self.key_cols, chunk - 1);
+        self.query(&sql).into_iter().next().map(|mut row| row.remove(0))
+    }
+
+    fn range(&self, low:Option<&str>, high:Option<&str>)->String {
+        let key = &self.key_cols;
+        match (low, high) {
+            (None, None) => "1=1".into(),
+            (Some(l), None) => format!("({key}) > ({l})"),
+            (None, Some(h)) => format!("({key}) <= ({h})"),
+            (Some(l), Some(h)) => format!("({key}) > ({l}) AND ({key}) <= ({h})"),
+        }
+    }
+
+    // count and xored row hashes of a key range, exact 64 bit integers
+    fn chunk_hash(&self, table:&str, range:&str)->(String, String) {
+        let sql = format!("SELECT COUNT(*), BIT_XOR(CAST(CONV(LEFT({}, 16), 16, 10) AS UNSIGNED)) FROM {table} WHERE {range}",
+            self.row_hash);
+        let row = self.query(&sql).into_iter().next().unwrap_or_default();
+        (row.first().cloned().unwrap_or_default(), row.get(1).cloned().unwrap_or_default())
+    }
+
+    fn row_hashes(&self, table:&str, range:&str)->BTreeMap<String, String> {
+        let sql = format!("SELECT {}, {} FROM {table} WHERE {range} ORDER BY {}",
+            self.key_lit, self.row_hash, self.key_cols);
+        self.query(&sql).into_iter()
+            .filter(|row| row.len() == 2)
+            .map(|mut row| {
+                let hash = row.pop().unwrap();
+                (row.pop().unwrap(), hash)
+            })
+            .collect()
+    }
+}
+
+// chunks are ranges of the left key, past the end of left of the right key, so no chunk holds more
+// than chunk rows of either table; a chunk whose count or hash differs is compared row by row
+pub fn diff_rows(dbe:&DatabaseEnv, left:&str, right:&str, chunk:usize)->Result<RowDiff, String> {
+    let key = primary_key(dbe, left);
+    if key.is_empty() {
+        return Err(format!("{left} has no primary key"));
+    }
+    if primary_key(dbe, right) != key {
+        return Err(format!("{right} has a different primary key than {left}"));
+    }
+    let cols = columns(dbe, left);
+    if columns(dbe, right) != cols {
+        return Err(format!("{right} has different columns than {left}"));
+    }
+    // numeric keys compare as numbers, not as strings
+    let info = column_info(dbe, left);
+    let quoted = key.iter()
+        .map(|k| match info.iter().find(|c| &c.name == k) {
+            Some(c) if c.is_integer() || c.data_type == "decimal" => quote_ident(k),
+            _ => format!("QUOTE({})", quote_ident(k)),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let hashed = cols.iter()
+        .map(|c| format!("{0}, ISNULL({0})", quote_ident(c)))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let t = Tables {
+        dbe,
+        key_lit: format!("CONCAT_WS(',', {quoted})"),
+        key_cols: ident_list(&key),
+        row_hash: format!("MD5(CONCAT_WS(0x1f, {hashed}))"),
+    };
+
+    let mut diff = RowDiff {
+        columns: cols,
+        key,
+        ..Default::default()
+    };
+    let mut low: Option<String> = None;
+    let mut n = 0;
+    loop {
+        let high = t.boundary(left, low.as_deref(), chunk)
+            .or_else(|| t.boundary(right, low.as_deref(), chunk));
+        let range = t.range(low.as_deref(), high.as_deref());
+        n += 1;
+        if t.chunk_hash(left, &range) != t.chunk_hash(right, &range) {
+            eprintln!("----- chunk {n} differs, comparing its rows -----");
+            let l = t.row_hashes(left, &range);
+            let r = t.row_hashes(right, &range);
+            for (k, hash) in &l {
+                match r.get(k) {
+                    None => diff.missing.push(k.clone()),
+                    Some(h) if h != hash => diff.changed.push(k.clone()),
+                    _ => {}
+                }
+            }
+            diff.extra.extend(r.keys().filter(|k| !l.contains_key(*k)).cloned());
+        }
+        if high.is_none() {
+            break;
+        }
+        low = high;
+    }
+    eprintln!("----- compared {n} chunks: {} missing, {} extra, {} changed -----",
+        diff.missing.len(), diff.extra.len(), diff.changed.len());
+    Ok(diff)
+}
+
+fn fetch(dbe:&DatabaseEnv, table:&str, diff:&RowDiff, keys:&[String], format:DiffFormat)->Vec<Vec<String>> {
+    if keys.is_empty() {
+        return Vec::new();
+    }
+    let select = match format {
+        DiffFormat::Sql => diff.columns.iter()
+            .map(|c| format!("QUOTE({})", quote_ident(c)))
+            .collect::<Vec<_>>()
+            .join(", "),
+        DiffFormat::Csv => ident_list(&diff.columns),
+    };
+    let key_cols = ident_list(&diff.key);
+    let mut rows = Vec::new();
+    for keys in keys.chunks(500) {
+        let keys = keys.iter().map(|k| format!("({k})")).collect::<Vec<_>>().join(", ");
+        let sql = format!("SELECT {select} FROM {table} WHERE ({key_cols}) IN ({keys}) ORDER BY {key_cols}");
+        let output = exe_sql_with_output(dbe, &sql);
+        assert!(output.status.success(), "{sql}");
+        rows.extend(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(|line| line.split('\t').map(batch_field).collect::<Vec<_>>()));
+    }
+    rows
+}
+
+// sql turns right into left, csv lists the rows with a leading kind column
+pub fn write_diff(dbe:&DatabaseEnv, left:&str, right:&str, diff:&RowDiff, format:DiffFormat, out:&mut impl Write)->std::io::Result<()> {
+    let cols = ident_list(&diff.columns);
+    let key_cols = ident_list(&diff.key);
+    match format {
+        DiffFormat::Sql => {
+            writeln!(out, "-- rows of {left} missing in {right}")?;
+            for row in fetch(dbe, left, diff, &diff.missing, format) {
+                writeln!(out, "INSERT INTO {right} ({cols}) VALUES ({});", row.join(", "))?;
+            }
+            writeln!(out, "-- rows changed between {left} and {right}")?;
+            for row in fetch(dbe, left, diff, &diff.changed, format) {
+                writeln!(out, "REPLACE INTO {right} ({cols}) VALUES ({});", row.join(", "))?;
+            }
+            writeln!(out, "-- rows of {right} not in {left}")?;
+            for k in &diff.extra {
+                writeln!(out, "DELETE FROM {right} WHERE ({key_cols}) = ({k});")?;
+            }
+        }
+        DiffFormat::Csv => {
+            let header = diff.columns.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",");
+            writeln!(out, "kind,{header}")?;
+            let parts = [
+                ("missing", left, &diff.missing),
+                ("changed", left, &diff.changed),
+                ("extra", right, &diff.extra),
+            ];
+            for (kind, table, keys) in parts {
+                for row in fetch(dbe, table, diff, keys, format) {
+                    let line = row.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(",");
+                    writeln!(out, "{kind},{line}")?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
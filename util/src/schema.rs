@@ -0,0 +1,224 @@
+use super::{batch_field, exe_sql_with_output};
+use super::panelenv::DatabaseEnv;
+
+fn single_column(dbe:&DatabaseEnv, sql:&str)->Vec<String> {
+    let output = exe_sql_with_output(dbe, sql);
+    assert!(output.status.success());
+    String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+// column names in their table order
+pub fn columns(dbe:&DatabaseEnv, table:&str)->Vec<String> {
+    let sql = format!("SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}' ORDER BY ORDINAL_POSITION");
+    single_column(dbe, &sql)
+}
+
+// primary key columns in key order, empty when the table has none
+pub fn primary_key(dbe:&DatabaseEnv, table:&str)->Vec<String> {
+    let sql = format!("SELECT COLUMN_NAME FROM information_schema.STATISTICS \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}' AND INDEX_NAME='PRIMARY' ORDER BY SEQ_IN_INDEX");
+    single_column(dbe, &sql)
+}
+
+// the unique keys, PRIMARY included, which do not hold column
+pub fn unique_keys_without(dbe:&DatabaseEnv, table:&str, column:&str)->Vec<String> {
+    let sql = format!("SELECT INDEX_NAME FROM information_schema.STATISTICS \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}' AND NON_UNIQUE=0 \
+        GROUP BY INDEX_NAME HAVING SUM(COLUMN_NAME='{column}')=0 ORDER BY INDEX_NAME");
+    single_column(dbe, &sql)
+}
+
+pub fn auto_increment_column(dbe:&DatabaseEnv, table:&str)->Option<String> {
+    let sql = format!("SELECT COLUMN_NAME FROM information_schema.COLUMNS \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}' AND EXTRA LIKE '%auto_increment%'");
+    single_column(dbe, &sql).into_iter().next()
+}
+
+// a column as information_schema.COLUMNS describes it
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ColumnInfo {
+    pub name: String,
+    // int, varchar, datetime ...
+    pub data_type: String,
+    // int(10) unsigned, varchar(32) ...
+    pub column_type: String,
+    pub nullable: bool,
+    pub max_length: Option<u64>,
+    // a VIRTUAL or STORED generated column, computed by the server and never inserted
+    #[serde(default)]
+    pub generated: bool,
+}
+
+impl ColumnInfo {
+    pub fn is_integer(&self)->bool {
+        matches!(self.data_type.as_str(), "tinyint" | "smallint" | "mediumint" | "int" | "bigint" | "year")
+    }
+
+    pub fn is_numeric(&self)->bool {
+        self.is_integer() || matches!(self.data_type.as_str(), "decimal" | "float" | "double" | "bit")
+    }
+
+    pub fn is_binary(&self)->bool {
+        matches!(self.data_type.as_str(), "binary" | "varbinary" | "tinyblob" | "blob" | "mediumblob" | "longblob"
+            | "geometry" | "point" | "linestring" | "polygon"
+            | "multipoint" | "multilinestring" | "multipolygon" | "geometrycollection")
+    }
+}
+
+pub fn column_info(dbe:&DatabaseEnv, table:&str)->Vec<ColumnInfo> {
+    let sql = format!("SELECT COLUMN_NAME, DATA_TYPE, COLUMN_TYPE, IS_NULLABLE, CHARACTER_MAXIMUM_LENGTH, EXTRA \
+        FROM information_schema.COLUMNS \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}' ORDER BY ORDINAL_POSITION");
+    single_column(dbe, &sql).iter()
+        .filter_map(|line| {
+            let fields = line.split('\t').map(batch_field).collect::<Vec<_>>();
+            let [name, data_type, column_type, nullable, max_length, extra] = fields.as_slice() else {
+                return None;
+            };
+            Some(ColumnInfo {
+                name: name.clone(),
+                data_type: data_type.to_lowercase(),
+                column_type: column_type.to_lowercase(),
+                nullable: nullable == "YES",
+                max_length: max_length.parse().ok(),
+                // DEFAULT_GENERATED only marks an expression default
+                generated: ["VIRTUAL GENERATED", "STORED GENERATED", "PERSISTENT"].iter()
+                    .any(|g| extra.to_uppercase().contains(g)),
+            })
+        })
+        .collect()
+}
+
+pub fn quote_ident(name:&str)->String {
+    format!("`{}`", name.replace('`', "``"))
+}
+
+pub fn ident_list(names:&[String])->String {
+    names.iter()
+        .map(|n| quote_ident(n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// SHOW CREATE TABLE, None when the table does not exist
+pub fn show_create(dbe:&DatabaseEnv, table:&str)->Option<String> {
+    let output = exe_sql_with_output(dbe, &format!("SHOW CREATE TABLE {table}"));
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (_, ddl) = stdout.trim_end_matches('\n').split_once('\t')?;
+    Some(batch_field(ddl))
+}
+
+// a CREATE TABLE split into comparable parts, the table name and AUTO_INCREMENT counter dropped
+#[derive(Debug, Default, PartialEq, Eq, Hash, Clone, serde::Serialize, serde::Deserialize)]
+pub struct TableSchema {
+    pub columns: Vec<(String, String)>,
+    pub indexes: Vec<String>,
+    pub options: String,
+}
+
+// one difference between an expected and an actual schema
+#[derive(Debug, PartialEq)]
+pub struct SchemaChange {
+    pub kind: &'static str,
+    pub item: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl TableSchema {
+    pub fn parse(ddl:&str)->Self {
+        let mut schema = Self::default();
+        for line in ddl.lines().skip(1) {
+            let line = line.trim().trim_end_matches(',');
+            if let Some(rest) = line.strip_prefix('`') {
+                let Some((name, def)) = rest.split_once("` ") else {
+                    continue;
+                };
+                schema.columns.push((name.to_string(), def.to_string()));
+            } else if let Some(options) = line.strip_prefix(')') {
+                schema.options = options.split_whitespace()
+                    .filter(|opt| !opt.starts_with("AUTO_INCREMENT="))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+            } else if !line.is_empty() {
+                schema.indexes.push(line.to_string());
+            }
+        }
+        schema.indexes.sort();
+        schema
+    }
+
+    pub fn diff(&self, actual:&TableSchema)->Vec<SchemaChange> {
+        let mut changes = Vec::new();
+        let change = |kind, item:&str, expected:&str, actual:&str| SchemaChange {
+            kind,
+            item: item.to_string(),
+            expected: expected.to_string(),
+            actual: actual.to_string(),
+        };
+        for (name, def) in &self.columns {
+            match actual.columns.iter().find(|(n, _)| n == name) {
+                None => changes.push(change("column", name, def, "")),
+                Some((_, d)) if d != def => changes.push(change("column", name, def, d)),
+                _ => {}
+            }
+        }
+        for (name, def) in &actual.columns {
+            if !self.columns.iter().any(|(n, _)| n == name) {
+                changes.push(change("column", name, "", def));
+            }
+        }
+        let names = |a:&[(String, String)]| a.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>();
+        let (expected_order, actual_order) = (names(&self.columns), names(&actual.columns));
+        if changes.is_empty() && expected_order != actual_order {
+            changes.push(change("order", "columns", &expected_order.join(","), &actual_order.join(",")));
+        }
+        for index in &self.indexes {
+            if !actual.indexes.contains(index) {
+                changes.push(change("index", index, index, ""));
+            }
+        }
+        for index in &actual.indexes {
+            if !self.indexes.contains(index) {
+                changes.push(change("index", index, "", index));
+            }
+        }
+        if self.options != actual.options {
+            changes.push(change("options", "table", &self.options, &actual.options));
+        }
+        changes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DDL: &str = "CREATE TABLE `sales1701` (\n  `id` int NOT NULL AUTO_INCREMENT,\n  `amount` int DEFAULT NULL,\n  PRIMARY KEY (`id`),\n  KEY `ix_amount` (`amount`)\n) ENGINE=InnoDB AUTO_INCREMENT=42 DEFAULT CHARSET=utf8mb4";
+
+    #[test]
+    fn parse_and_diff() {
+        let a = TableSchema::parse(DDL);
+        assert_eq!(a.columns.len(), 2);
+        assert_eq!(a.indexes, vec!["KEY `ix_amount` (`amount`)", "PRIMARY KEY (`id`)"]);
+        assert_eq!(a.options, "ENGINE=InnoDB DEFAULT CHARSET=utf8mb4");
+
+        let ddl = DDL.replace("AUTO_INCREMENT=42", "AUTO_INCREMENT=7").replace("sales1701", "sales1702");
+        assert_eq!(TableSchema::parse(&ddl), a);
+
+        let ddl = DDL.replace("`amount` int", "`amount` bigint")
+            .replace("utf8mb4", "latin1")
+            .replace(",\n  KEY `ix_amount` (`amount`)", "");
+        let changes = a.diff(&TableSchema::parse(&ddl));
+        let kinds = changes.iter().map(|c| c.kind).collect::<Vec<_>>();
+        assert_eq!(kinds, vec!["column", "index", "options"]);
+        assert_eq!(changes[0].actual, "bigint DEFAULT NULL");
+    }
+}
@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+
+use super::panelenv::{DatabaseEnv, TableRule};
+use super::report::Row;
+use super::schema::{show_create, TableSchema};
+
+// a month whose schema differs from the majority of the months of its name
+#[derive(Debug, serde::Serialize)]
+pub struct SchemaDeviation {
+    pub name: String,
+    pub table: String,
+    pub reference: String,
+    pub kind: String,
+    pub item: String,
+    pub expected: String,
+    pub actual: String,
+}
+
+impl Row for SchemaDeviation {
+    const COLUMNS: &'static [&'static str] = &["name", "table", "reference", "kind", "item", "expected", "actual"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.name.clone(),
+            self.table.clone(),
+            self.reference.clone(),
+            self.kind.clone(),
+            self.item.clone(),
+            self.expected.clone(),
+            self.actual.clone(),
+        ]
+    }
+}
+
+pub fn check(dbe:&DatabaseEnv, rule:&TableRule, postfix:&str)->Vec<SchemaDeviation> {
+    let mut deviations = Vec::new();
+    for name in &rule.names {
+        let mut schemas = Vec::new();
+        for year in &rule.years {
+            for month in &rule.months {
+                let table = format!("{name}{year}{month}{postfix}");
+                if let Some(ddl) = show_create(dbe, &table) {
+                    schemas.push((table, TableSchema::parse(&ddl)));
+                }
+            }
+        }
+        deviations.extend(check_name(name, &schemas));
+    }
+    deviations
+}
+
+// the schema shared by most tables is the reference, ties go to the earliest table
+pub fn check_name(name:&str, schemas:&[(String, TableSchema)])->Vec<SchemaDeviation> {
+    let mut groups: HashMap<&TableSchema, Vec<&str>> = HashMap::new();
+    for (table, schema) in schemas {
+        groups.entry(schema).or_default().push(table);
+    }
+    let Some((reference, members)) = groups.iter()
+        .max_by(|(_, a), (_, b)| a.len().cmp(&b.len()).then(b[0].cmp(a[0]))) else {
+        return Vec::new();
+    };
+    eprintln!("----- {name}: {} schemas over {} tables, {} share the majority -----",
+        groups.len(), schemas.len(), members.len());
+    let mut deviations = Vec::new();
+    for (table, schema) in schemas {
+        if schema == *reference {
+            continue;
+        }
+        for change in reference.diff(schema) {
+            deviations.push(SchemaDeviation {
+                name: name.to_string(),
+                table: table.clone(),
+                reference: members[0].to_string(),
+                kind: change.kind.to_string(),
+                item: change.item,
+                expected: change.expected,
+                actual: change.actual,
+            });
+        }
+    }
+    deviations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn majority_is_reference() {
+        let ddl = "CREATE TABLE `t` (\n  `id` int NOT NULL,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB";
+        let wide = "CREATE TABLE `t` (\n  `id` int NOT NULL,\n  `foo` int DEFAULT NULL,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB";
+        let schemas = vec![
+            ("t1701".to_string(), TableSchema::parse(wide)),
+            ("t1702".to_string(), TableSchema::parse(ddl)),
+            ("t1703".to_string(), TableSchema::parse(ddl)),
+        ];
+        let deviations = check_name("t", &schemas);
+        assert_eq!(deviations.len(), 1);
+        assert_eq!(deviations[0].table, "t1701");
+        assert_eq!(deviations[0].reference, "t1702");
+        assert_eq!(deviations[0].item, "foo");
+    }
+}
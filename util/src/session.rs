@@ -0,0 +1,138 @@
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, ExitStatus, Stdio};
+
+use super::batch_field;
+use super::panelenv::DatabaseEnv;
+
+// what the server answered to one statement
+#[derive(Debug, Default)]
+pub struct Outcome {
+    pub failed: bool,
+    // rows changed by the statement, None for one which does not change rows
+    pub affected: Option<u64>,
+    // the result rows, tab separated as `mysql -NB` prints them
+    pub rows: Vec<String>,
+}
+
+// one mysql connection kept open across statements, so transactions, USE, temporary tables
+// and LOCK TABLES hold until it closes; --force keeps it open after a failed statement,
+// --quick streams result rows instead of buffering them
+pub struct Session {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+    acks: u64,
+}
+
+// printed after every statement with the errors it raised and the rows it changed,
+// ends the rows of the statement
+const ACK: &str = "migrate-ack";
+
+impl Session {
+    pub fn open(env_rw:&DatabaseEnv)->Result<Self, String> {
+        let url = &env_rw.url;
+        let urlp = format!("-h{url}");
+        let user = &env_rw.user;
+        let userp = format!("-u{}",user);
+        let passwd = &env_rw.passwd;
+        let database = &env_rw.database;
+        let databasep  = format!("-D{}",database);
+        let passwd_set = format!("export MYSQL_PWD={passwd}");
+        let mysql_cmd = format!("mysql -A -NB --force --unbuffered --quick --default-character-set=utf8mb4 {urlp} {userp} {databasep}");
+        let cmd = format!("{passwd_set};exec {mysql_cmd}");
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(cmd)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("mysql: {e}"))?;
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take().map(BufReader::new);
+        match stdout {
+            Some(stdout) => Ok(Self { child, stdin, stdout, acks: 0 }),
+            None => Err("mysql: no stdout".into()),
+        }
+    }
+
+    // sends one statement and waits until the server answered it, Err when the connection is gone
+    pub fn execute(&mut self, sql:&str, delimiter:&str)->Result<Outcome, String> {
+        let mut rows = Vec::new();
+        let mut outcome = self.send(sql, delimiter, |row| {
+            rows.push(row.to_string());
+            Ok(())
+        })?;
+        outcome.rows = rows;
+        Ok(outcome)
+    }
+
+    // like execute, but each result row is handed to each as it arrives instead of being kept,
+    // a failure of each is returned once the server answered
+    pub fn each_row(&mut self, sql:&str, each:impl FnMut(&str)->std::io::Result<()>)->Result<Outcome, String> {
+        self.send(sql, ";", each)
+    }
+
+    fn send(&mut self, sql:&str, delimiter:&str, mut each:impl FnMut(&str)->std::io::Result<()>)->Result<Outcome, String> {
+        self.acks += 1;
+        let ack = format!("{ACK}-{}", self.acks);
+        let body = if delimiter == ";" {
+            format!("{sql};\nSELECT @@error_count, ROW_COUNT(), '{ack}';\n")
+        } else {
+            format!("DELIMITER {delimiter}\n{sql}{delimiter}\nDELIMITER ;\nSELECT @@error_count, ROW_COUNT(), '{ack}';\n")
+        };
+        let stdin = self.stdin.as_mut().ok_or("the session is closed")?;
+        stdin.write_all(body.as_bytes())
+            .and_then(|_| stdin.flush())
+            .map_err(|e| format!("sending to mysql: {e}"))?;
+        let mut outcome = Outcome::default();
+        let mut failure = None;
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self.stdout.read_line(&mut line).map_err(|e| format!("reading from mysql: {e}"))?;
+            if read == 0 {
+                return Err("mysql closed the connection".into());
+            }
+            let line = line.trim_end_matches(['\n', '\r']);
+            match line.strip_suffix(&ack).and_then(|counts| counts.strip_suffix('\t')) {
+                Some(counts) => {
+                    let (errors, affected) = counts.split_once('\t').unwrap_or((counts, ""));
+                    outcome.failed = errors != "0";
+                    outcome.affected = affected.parse().ok();
+                    return match failure {
+                        Some(err) => Err(err),
+                        None => Ok(outcome),
+                    };
+                }
+                None if failure.is_none() => {
+                    if let Err(err) = each(line) {
+                        failure = Some(format!("{err}"));
+                    }
+                }
+                None => {}
+            }
+        }
+    }
+
+    // the fields of the first row, Err when the statement failed or returned nothing
+    pub fn row(&mut self, sql:&str)->Result<Vec<String>, String> {
+        let outcome = self.execute(sql, ";")?;
+        match outcome.rows.first() {
+            Some(row) if !outcome.failed => Ok(row.split('\t').map(batch_field).collect()),
+            _ => Err(format!("{sql} failed")),
+        }
+    }
+
+    // ends the connection, an open transaction is rolled back by the server
+    pub fn close(mut self)->ExitStatus {
+        drop(self.stdin.take());
+        self.child.wait().expect("failed to wait on process")
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use super::clock;
+use super::panelenv::{DatabaseEnv, TableRule};
+use super::report::Row;
+use super::schema::{show_create, TableSchema};
+
+// {dir}/index.toml lists what {dir}/{table}.sql files were taken
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotIndex {
+    pub database: String,
+    // the postfix the tables were taken with, `live` compares against the same one
+    #[serde(default)]
+    pub postfix: String,
+    pub created_at: String,
+    pub tables: Vec<SnapshotTable>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SnapshotTable {
+    pub table: String,
+    pub file: String,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SchemaDiffRow {
+    pub table: String,
+    pub kind: String,
+    pub item: String,
+    pub before: String,
+    pub after: String,
+}
+
+impl Row for SchemaDiffRow {
+    const COLUMNS: &'static [&'static str] = &["table", "kind", "item", "before", "after"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.table.clone(),
+            self.kind.clone(),
+            self.item.clone(),
+            self.before.clone(),
+            self.after.clone(),
+        ]
+    }
+}
+
+pub fn default_dir(basedir:&str, database:&str)->String {
+    format!("{basedir}/schema/{database}-{}", clock::now_stamp())
+}
+
+pub fn take(dbe:&DatabaseEnv, rule:&TableRule, postfix:&str, dir:&str)->std::io::Result<SnapshotIndex> {
+    std::fs::create_dir_all(dir)?;
+    let mut index = SnapshotIndex {
+        database: dbe.database.clone(),
+        postfix: postfix.to_string(),
+        created_at: clock::now_stamp(),
+        tables: Vec::new(),
+    };
+    for table in live_tables(rule, postfix) {
+        let Some(ddl) = show_create(dbe, &table) else {
+            continue;
+        };
+        let file = format!("{table}.sql");
+        std::fs::write(Path::new(dir).join(&file), format!("{ddl};\n"))?;
+        index.tables.push(SnapshotTable { table, file });
+    }
+    let content = toml::to_string(&index).expect("serialize snapshot index");
+    std::fs::write(Path::new(dir).join("index.toml"), content)?;
+    Ok(index)
+}
+
+pub fn read_index(dir:&str)->std::io::Result<SnapshotIndex> {
+    let content = std::fs::read_to_string(Path::new(dir).join("index.toml"))?;
+    toml::from_str(&content).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+pub fn load(dir:&str)->std::io::Result<BTreeMap<String, TableSchema>> {
+    let index = read_index(dir)?;
+    let mut schemas = BTreeMap::new();
+    for t in index.tables {
+        let ddl = std::fs::read_to_string(Path::new(dir).join(&t.file))?;
+        schemas.insert(t.table, TableSchema::parse(ddl.trim_end().trim_end_matches(';')));
+    }
+    Ok(schemas)
+}
+
+pub fn live_tables(rule:&TableRule, postfix:&str)->Vec<String> {
+    rule.tables().into_iter().map(|table| format!("{table}{postfix}")).collect()
+}
+
+pub fn live(dbe:&DatabaseEnv, rule:&TableRule, postfix:&str)->BTreeMap<String, TableSchema> {
+    live_tables(rule, postfix).into_iter()
+        .filter_map(|table| {
+            let ddl = show_create(dbe, &table)?;
+            Some((table, TableSchema::parse(&ddl)))
+        })
+        .collect()
+}
+
+pub fn diff(before:&BTreeMap<String, TableSchema>, after:&BTreeMap<String, TableSchema>)->Vec<SchemaDiffRow> {
+    let mut rows = Vec::new();
+    let mut tables = before.keys().chain(after.keys()).collect::<Vec<_>>();
+    tables.sort();
+    tables.dedup();
+    for table in tables {
+        let row = |kind:&str, item:&str, before:&str, after:&str| SchemaDiffRow {
+            table: table.clone(),
+            kind: kind.into(),
+            item: item.into(),
+            before: before.into(),
+            after: after.into(),
+        };
+        match (before.get(table), after.get(table)) {
+            (Some(_), None) => rows.push(row("removed", "table", table, "")),
+            (None, Some(_)) => rows.push(row("added", "table", "", table)),
+            (Some(b), Some(a)) => {
+                for change in b.diff(a) {
+                    rows.push(row(change.kind, &change.item, &change.expected, &change.actual));
+                }
+            }
+            (None, None) => {}
+        }
+    }
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn postfixed_snapshot_against_itself() {
+        let dir = std::env::temp_dir().join(format!("snapshot-test-{}", std::process::id()));
+        let dir = dir.to_str().unwrap();
+        let rule = TableRule { names: vec!["sales"], years: vec!["17"], months: vec!["01", "02"] };
+        let ddl = |table:&str| format!("CREATE TABLE `{table}` (\n  `id` int NOT NULL,\n  PRIMARY KEY (`id`)\n) ENGINE=InnoDB");
+        let index = SnapshotIndex {
+            database: "panel".into(),
+            postfix: "_old".into(),
+            created_at: "20170301000000".into(),
+            tables: live_tables(&rule, "_old").into_iter()
+                .map(|table| SnapshotTable { file: format!("{table}.sql"), table })
+                .collect(),
+        };
+        std::fs::create_dir_all(dir).unwrap();
+        for t in &index.tables {
+            std::fs::write(Path::new(dir).join(&t.file), format!("{};\n", ddl(&t.table))).unwrap();
+        }
+        std::fs::write(Path::new(dir).join("index.toml"), toml::to_string(&index).unwrap()).unwrap();
+
+        let before = load(dir).unwrap();
+        let postfix = read_index(dir).unwrap().postfix;
+        let live = live_tables(&rule, &postfix).into_iter()
+            .map(|table| (table.clone(), TableSchema::parse(&ddl(&table))))
+            .collect();
+        std::fs::remove_dir_all(dir).unwrap();
+        assert!(diff(&before, &live).is_empty());
+    }
+}
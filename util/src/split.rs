@@ -0,0 +1,150 @@
+use super::{create_empty, count_rows, exe_sql, exe_sql_with_output, table_exists};
+use super::panelenv::DatabaseEnv;
+use super::report::{opt, Row};
+use super::schema::{ident_list, primary_key, quote_ident};
+use super::session::{Outcome, Session};
+
+#[derive(Debug, serde::Serialize)]
+pub struct SplitReport {
+    pub table: String,
+    pub rows: Option<u64>,
+    pub moved: Option<u64>,
+    pub status: String,
+}
+
+impl Row for SplitReport {
+    const COLUMNS: &'static [&'static str] = &["table", "rows", "moved", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.table.clone(),
+            opt(self.rows),
+            opt(self.moved),
+            self.status.clone(),
+        ]
+    }
+}
+
+fn query(dbe:&DatabaseEnv, sql:&str)->Vec<String> {
+    let output = exe_sql_with_output(dbe, sql);
+    assert!(output.status.success(), "{sql}");
+    String::from_utf8_lossy(&output.stdout).lines().map(String::from).collect()
+}
+
+// the half open range [first day, first day of the next month) of yymm
+pub fn month_range(column:&str, yymm:&str)->String {
+    let y = 2000 + yymm[..2].parse::<i64>().unwrap_or(0);
+    let m = yymm[2..].parse::<i64>().unwrap_or(1);
+    let (ny, nm) = if m == 12 { (y + 1, 1) } else { (y, m + 1) };
+    let column = quote_ident(column);
+    format!("{column} >= '{y:04}-{m:02}-01' AND {column} < '{ny:04}-{nm:02}-01'")
+}
+
+fn is_partitioned(dbe:&DatabaseEnv, table:&str)->bool {
+    let sql = format!("SELECT COUNT(*) FROM information_schema.PARTITIONS \
+        WHERE TABLE_SCHEMA=DATABASE() AND TABLE_NAME='{table}' AND PARTITION_NAME IS NOT NULL");
+    query(dbe, &sql).first().is_some_and(|n| n != "0")
+}
+
+// one chunk is inserted and deleted in a transaction, committed only when both touched
+// the same number of rows, returns the rows moved
+fn move_chunk(session:&mut Session, source:&str, table:&str, range:&str, order:&str, chunk:usize)->Result<u64, String> {
+    let mut run = |sql:&str| session.execute(sql, ";").and_then(|outcome| match outcome {
+        Outcome { failed: false, affected, .. } => Ok(affected.unwrap_or(0)),
+        _ => Err(format!("{sql} failed")),
+    });
+    let moved = run("START TRANSACTION").and_then(|_| {
+        let inserted = run(&format!("INSERT INTO {table} SELECT * FROM {source} WHERE {range} ORDER BY {order} LIMIT {chunk}"))?;
+        let deleted = run(&format!("DELETE FROM {source} WHERE {range} ORDER BY {order} LIMIT {chunk}"))?;
+        if inserted != deleted {
+            return Err(format!("{inserted} rows inserted but {deleted} deleted"));
+        }
+        Ok(inserted)
+    });
+    match moved {
+        Ok(rows) => run("COMMIT").map(|_| rows),
+        Err(err) => {
+            run("ROLLBACK")?;
+            Err(format!("{err}, rolled back"))
+        }
+    }
+}
+
+// rows of source are moved into {name}yymm by their month, chunk by chunk in primary key order,
+// each chunk is inserted and deleted in one transaction
+pub fn split(dbe:&DatabaseEnv, source:&str, column:&str, name:&str, chunk:usize)->Result<Vec<SplitReport>, String> {
+    let key = primary_key(dbe, source);
+    if key.is_empty() {
+        return Err(format!("{source} has no primary key"));
+    }
+    let order = ident_list(&key);
+    let partitioned = is_partitioned(dbe, source);
+    let months = query(dbe, &format!("SELECT DISTINCT DATE_FORMAT({}, '%y%m') FROM {source} \
+        WHERE {0} IS NOT NULL ORDER BY 1", quote_ident(column)));
+
+    let mut session = Session::open(dbe)?;
+    let mut reports = Vec::new();
+    for yymm in months {
+        let table = format!("{name}{yymm}");
+        let range = month_range(column, &yymm);
+        let report = |rows, moved, status:&str| SplitReport {
+            table: table.clone(),
+            rows,
+            moved,
+            status: status.into(),
+        };
+        if !table_exists(dbe, &table) {
+            let created = create_empty(dbe, source, &table).success()
+                && (!partitioned || exe_sql(dbe, &format!("ALTER TABLE {table} REMOVE PARTITIONING")).success());
+            if !created {
+                reports.push(report(None, None, "create failed"));
+                break;
+            }
+        }
+        let rows = query(dbe, &format!("SELECT COUNT(*) FROM {source} WHERE {range}"))
+            .first()
+            .and_then(|n| n.parse::<u64>().ok());
+        let before = count_rows(dbe, &table);
+        let mut moved = 0;
+        let mut failed = false;
+        loop {
+            match move_chunk(&mut session, source, &table, &range, &order, chunk) {
+                Ok(0) => break,
+                Ok(rows) => {
+                    moved += rows;
+                    eprintln!("----- {table}: {moved} rows moved -----");
+                }
+                Err(err) => {
+                    eprintln!("----- {table}: {err} -----");
+                    failed = true;
+                    break;
+                }
+            }
+        }
+        let after = count_rows(dbe, &table);
+        let verified = match (rows, before, after) {
+            (Some(rows), Some(before), Some(after)) => rows == moved && after == before + moved,
+            _ => false,
+        };
+        let status = match (failed, verified) {
+            (true, _) => "move failed",
+            (false, true) => "moved",
+            (false, false) => "count mismatch",
+        };
+        reports.push(report(rows, Some(moved), status));
+        if status != "moved" {
+            break;
+        }
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn month_ranges() {
+        assert_eq!(month_range("day", "1702"), "`day` >= '2017-02-01' AND `day` < '2017-03-01'");
+        assert_eq!(month_range("day", "1712"), "`day` >= '2017-12-01' AND `day` < '2018-01-01'");
+    }
+}
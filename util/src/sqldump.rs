@@ -0,0 +1,179 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::{backup, batch_field};
+use super::dump::{DumpMode, DumpOptions};
+use super::panelenv::DatabaseEnv;
+use super::session::Session;
+use super::schema::{column_info, ident_list, primary_key, quote_ident, show_create, ColumnInfo};
+
+// bytes of one multi-row INSERT unless extended_insert says otherwise
+pub const INSERT_BYTES: u64 = 1024 * 1024;
+
+// the server renders each value as a SQL literal, so the file never depends on client side formatting
+fn literal_expr(col:&ColumnInfo)->String {
+    let name = quote_ident(&col.name);
+    if col.is_binary() {
+        format!("IF({name} IS NULL, 'NULL', CONCAT('0x', HEX({name})))")
+    } else if col.data_type == "bit" {
+        format!("IF({name} IS NULL, 'NULL', CONCAT('b''', BIN({name}), ''''))")
+    } else {
+        format!("QUOTE({name})")
+    }
+}
+
+// the SELECT rendering every row as its VALUES tuple fields, ordered by the primary key
+// or by every column when there is none
+pub fn select_sql(table:&str, cols:&[ColumnInfo], key:&[String], filter:Option<&str>)->String {
+    let select = cols.iter().map(literal_expr).collect::<Vec<_>>().join(", ");
+    let order = if key.is_empty() {
+        ident_list(&cols.iter().map(|c| c.name.clone()).collect::<Vec<_>>())
+    } else {
+        ident_list(key)
+    };
+    format!("SELECT {select} FROM {table}{} ORDER BY {order}", where_clause(filter))
+}
+
+fn where_clause(filter:Option<&str>)->String {
+    filter.map(|f| format!(" WHERE {f}")).unwrap_or_default()
+}
+
+// the columns a dump holds, generated columns are computed again when the rows are inserted
+pub fn dumped_columns(dbe:&DatabaseEnv, table:&str)->Vec<ColumnInfo> {
+    column_info(dbe, table).into_iter().filter(|c| !c.generated).collect()
+}
+
+// turns the tuples into INSERTs of at most limit bytes, 0 writes one INSERT per row
+pub struct Inserts<'w, W:Write> {
+    out: &'w mut W,
+    head: String,
+    limit: u64,
+    pending: u64,
+}
+
+impl<'w, W:Write> Inserts<'w, W> {
+    pub fn new(out:&'w mut W, table:&str, cols:&[ColumnInfo], limit:u64)->Self {
+        let names = cols.iter().map(|c| c.name.clone()).collect::<Vec<_>>();
+        Self {
+            out,
+            head: format!("INSERT INTO {} ({}) VALUES ", quote_ident(table), ident_list(&names)),
+            limit,
+            pending: 0,
+        }
+    }
+
+    fn push(&mut self, tuple:&str)->std::io::Result<()> {
+        let len = tuple.len() as u64 + 1;
+        if self.pending > 0 && (self.limit == 0 || self.pending + len > self.limit) {
+            self.finish()?;
+        }
+        if self.pending == 0 {
+            write!(self.out, "{}{tuple}", self.head)?;
+            self.pending = self.head.len() as u64 + len;
+        } else {
+            write!(self.out, ",{tuple}")?;
+            self.pending += len;
+        }
+        Ok(())
+    }
+
+    pub fn finish(&mut self)->std::io::Result<()> {
+        if self.pending > 0 {
+            writeln!(self.out, ";")?;
+            self.pending = 0;
+        }
+        Ok(())
+    }
+
+    // ends the pending INSERT and hands everything written to the file
+    pub fn flush(&mut self)->std::io::Result<()> {
+        self.finish()?;
+        self.out.flush()
+    }
+}
+
+// one `mysql -NB` row of VALUES tuple fields as an INSERT tuple
+pub fn write_row(line:&str, inserts:&mut Inserts<impl Write>)->std::io::Result<()> {
+    let tuple = line.split('\t').map(batch_field).collect::<Vec<_>>().join(",");
+    inserts.push(&format!("({tuple})"))
+}
+
+// CREATE TABLE plus batched INSERTs in primary key order, replayable by dump_in;
+// no mysqldump is needed but the rows are read through the mysql client, which still must be installed.
+// The count and the rows are read in one consistent snapshot, the rows written must match the count.
+// Returns the rows written and, for a complete dump, the backup::state_sql checksum of that snapshot
+pub fn dump(dbe:&DatabaseEnv, table:&str, outfile:&str, options:&DumpOptions)->Result<(u64, Option<String>), String> {
+    let mode = options.mode.unwrap_or_default();
+    let cols = dumped_columns(dbe, table);
+    let ddl = show_create(dbe, table).ok_or_else(|| format!("{table} does not exist"))?;
+    let io_err = |e:std::io::Error| format!("{outfile}: {e}");
+    let mut out = BufWriter::new(File::create(outfile).map_err(io_err)?);
+    writeln!(out, "-- {}.{table} dumped by migrate", dbe.database).map_err(io_err)?;
+    writeln!(out, "SET NAMES utf8mb4;\nSET TIME_ZONE='+00:00';\nSET FOREIGN_KEY_CHECKS=0;\nSET UNIQUE_CHECKS=0;").map_err(io_err)?;
+    if mode != DumpMode::Data {
+        writeln!(out, "DROP TABLE IF EXISTS {};\n{ddl};", quote_ident(table)).map_err(io_err)?;
+    }
+    let mut rows = 0;
+    let mut checksum = None;
+    if mode != DumpMode::Schema {
+        let mut session = Session::open(dbe)?;
+        // TIMESTAMP values are read in the zone the file sets
+        if session.execute("SET TIME_ZONE='+00:00'", ";")?.failed {
+            return Err("failed to set the session time zone".into());
+        }
+        let started = session.execute("START TRANSACTION WITH CONSISTENT SNAPSHOT", ";")?;
+        if started.failed {
+            return Err(format!("failed to start a snapshot of {table}"));
+        }
+        let filter = options.filter.as_deref();
+        let expected = if options.is_complete() {
+            let (expected, state) = backup::session_state(&mut session, dbe, table)?;
+            checksum = Some(state);
+            expected
+        } else {
+            session.row(&format!("SELECT COUNT(*) FROM {table}{}", where_clause(filter)))?
+                .first()
+                .and_then(|n| n.parse::<u64>().ok())
+                .ok_or_else(|| format!("failed to count {table}"))?
+        };
+        let mut inserts = Inserts::new(&mut out, table, &cols, options.extended_insert.unwrap_or(INSERT_BYTES));
+        let sql = select_sql(table, &cols, &primary_key(dbe, table), filter);
+        let read = session.each_row(&sql, |line| {
+            rows += 1;
+            write_row(line, &mut inserts)
+        })?;
+        inserts.finish().map_err(io_err)?;
+        if read.failed {
+            return Err(format!("reading {table} failed"));
+        }
+        if rows != expected {
+            return Err(format!("{outfile} holds {rows} rows, {table} {expected}"));
+        }
+        session.close();
+    }
+    writeln!(out, "SET UNIQUE_CHECKS=1;\nSET FOREIGN_KEY_CHECKS=1;").map_err(io_err)?;
+    out.flush().map_err(io_err)?;
+    Ok((rows, checksum))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batched_inserts() {
+        let mut out = Vec::new();
+        let mut inserts = Inserts {
+            out: &mut out,
+            head: "INSERT INTO `t` (`id`, `note`, `data`) VALUES ".into(),
+            limit: 88,
+            pending: 0,
+        };
+        for line in ["'1'\t'a\\tb'\tNULL", "'2'\t'c'\t0x00FF", "'3'\t'd'\tNULL"] {
+            write_row(line, &mut inserts).unwrap();
+        }
+        inserts.finish().unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "INSERT INTO `t` (`id`, `note`, `data`) VALUES ('1','a\tb',NULL),('2','c',0x00FF);\n\
+            INSERT INTO `t` (`id`, `note`, `data`) VALUES ('3','d',NULL);\n");
+    }
+}
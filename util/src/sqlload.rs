@@ -0,0 +1,316 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use super::{exe_sql, is_empty, table_exists};
+use super::panelenv::DatabaseEnv;
+use super::report::Row;
+use super::schema::quote_ident;
+use super::session::Session;
+
+// [load]
+// builtin = true
+// on_error = "continue"   # "stop" (default) at the first failed statement
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct LoadEnv {
+    #[serde(default)]
+    pub builtin: bool,
+    #[serde(default)]
+    pub on_error: OnError,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OnError {
+    #[default]
+    Stop,
+    Continue,
+}
+
+pub struct LoadOptions {
+    pub on_error: OnError,
+    // statements naming the table `from` name the SQL identifier `to` instead
+    pub target: Option<(String, String)>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct LoadReport {
+    pub file: String,
+    pub statements: u64,
+    pub failed: u64,
+    pub bytes: u64,
+    pub status: String,
+}
+
+impl Row for LoadReport {
+    const COLUMNS: &'static [&'static str] = &["file", "statements", "failed", "bytes", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.file.clone(),
+            self.statements.to_string(),
+            self.failed.to_string(),
+            self.bytes.to_string(),
+            self.status.clone(),
+        ]
+    }
+}
+
+// one statement of the file without its delimiter, offset is where it ends
+#[derive(Debug, PartialEq)]
+pub struct Statement {
+    pub sql: String,
+    pub delimiter: String,
+    pub offset: u64,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Normal,
+    Quoted(u8),
+    Escaped(u8),
+    BlockComment,
+}
+
+// splits on the delimiter outside of quotes and comments, DELIMITER lines change it,
+// comments are dropped except the executable /*! ... */ ones
+pub fn split(input:impl BufRead, mut each:impl FnMut(Statement)->bool)->std::io::Result<()> {
+    let mut delimiter = b";".to_vec();
+    let mut buf: Vec<u8> = Vec::new();
+    let mut state = State::Normal;
+    let mut offset = 0;
+    for line in input.split(b'\n') {
+        let mut line = line?;
+        offset += line.len() as u64 + 1;
+        line.push(b'\n');
+        if state == State::Normal && buf.iter().all(u8::is_ascii_whitespace) {
+            let text = String::from_utf8_lossy(&line);
+            let trimmed = text.trim();
+            if trimmed.len() > 10 && trimmed.get(..10).is_some_and(|d| d.eq_ignore_ascii_case("delimiter ")) {
+                delimiter = trimmed[10..].trim().as_bytes().to_vec();
+                buf.clear();
+                continue;
+            }
+        }
+        let mut i = 0;
+        while i < line.len() {
+            let b = line[i];
+            let next = line.get(i + 1).copied();
+            i += 1;
+            match state {
+                State::Escaped(q) => {
+                    buf.push(b);
+                    state = State::Quoted(q);
+                }
+                State::Quoted(q) => {
+                    buf.push(b);
+                    if b == q {
+                        state = State::Normal;
+                    } else if b == b'\\' && q != b'`' {
+                        state = State::Escaped(q);
+                    }
+                }
+                State::BlockComment => {
+                    if b == b'*' && next == Some(b'/') {
+                        i += 1;
+                        state = State::Normal;
+                    }
+                }
+                State::Normal => match (b, next) {
+                    (b'-', Some(b'-')) if line.get(i + 1).is_none_or(u8::is_ascii_whitespace) => break,
+                    (b'#', _) => break,
+                    (b'/', Some(b'*')) if line.get(i + 1) != Some(&b'!') => {
+                        i += 1;
+                        state = State::BlockComment;
+                    }
+                    (b'\'' | b'"' | b'`', _) => {
+                        buf.push(b);
+                        state = State::Quoted(b);
+                    }
+                    _ => {
+                        buf.push(b);
+                        if buf.ends_with(&delimiter) {
+                            buf.truncate(buf.len() - delimiter.len());
+                            let sql = String::from_utf8_lossy(&buf).trim().to_string();
+                            buf.clear();
+                            if sql.is_empty() {
+                                continue;
+                            }
+                            let statement = Statement {
+                                sql,
+                                delimiter: String::from_utf8_lossy(&delimiter).into_owned(),
+                                offset: offset - (line.len() - i) as u64,
+                            };
+                            if !each(statement) {
+                                return Ok(());
+                            }
+                        }
+                    }
+                },
+            }
+        }
+        if state == State::Normal && !buf.is_empty() && !buf.ends_with(b"\n") {
+            buf.push(b'\n');
+        }
+    }
+    let sql = String::from_utf8_lossy(&buf).trim().to_string();
+    if !sql.is_empty() {
+        each(Statement {
+            sql,
+            delimiter: String::from_utf8_lossy(&delimiter).into_owned(),
+            offset,
+        });
+    }
+    Ok(())
+}
+
+// the first mention of the table before any string literal names `to` instead
+pub fn retarget(sql:&str, from:&str, to:&str)->String {
+    let end = sql.find(['\'', '"']).unwrap_or(sql.len());
+    let region = &sql[..end];
+    let quoted = format!("`{from}`");
+    if let Some(pos) = region.find(&quoted) {
+        return format!("{}{to}{}", &sql[..pos], &sql[pos + quoted.len()..]);
+    }
+    let is_ident = |c:char| c.is_ascii_alphanumeric() || c == '_' || c == '$';
+    let found = region.match_indices(from).find(|(pos, _)| {
+        let before = region[..*pos].chars().next_back();
+        let after = region[pos + from.len()..].chars().next();
+        !before.is_some_and(is_ident) && before != Some('.') && !after.is_some_and(is_ident)
+    });
+    match found {
+        Some((pos, _)) => format!("{}{to}{}", &sql[..pos], &sql[pos + from.len()..]),
+        None => sql.to_string(),
+    }
+}
+
+// the statements run one after another over a single mysql session, so SET, USE, transactions
+// and LOCK TABLES of the file hold for the statements after them
+pub fn load_file(dbe:&DatabaseEnv, path:&str, options:&LoadOptions)->LoadReport {
+    let mut report = LoadReport::refused(path, String::new());
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) => {
+            report.status = format!("{err}");
+            return report;
+        }
+    };
+    let total = file.metadata().map(|m| m.len()).unwrap_or(0);
+    let mut session = match Session::open(dbe) {
+        Ok(session) => session,
+        Err(err) => {
+            report.status = err;
+            return report;
+        }
+    };
+    let mut stopped = None;
+    let result = split(BufReader::new(file), |statement| {
+        report.bytes = statement.offset.min(total);
+        let sql = match &options.target {
+            Some((from, to)) => retarget(&statement.sql, from, to),
+            None => statement.sql,
+        };
+        report.statements += 1;
+        let failed = match session.execute(&sql, &statement.delimiter) {
+            Ok(outcome) => outcome.failed,
+            Err(err) => {
+                report.failed += 1;
+                stopped = Some(format!("{err} at statement {}, byte {}", report.statements, report.bytes));
+                return false;
+            }
+        };
+        if failed {
+            report.failed += 1;
+            let preview = sql.chars().take(120).collect::<String>();
+            eprintln!("----- statement {} ending at byte {} failed: {preview} -----", report.statements, report.bytes);
+            if options.on_error == OnError::Stop {
+                stopped = Some(format!("stopped at statement {}, byte {}", report.statements, report.bytes));
+                return false;
+            }
+        }
+        if report.statements.is_multiple_of(100) {
+            eprintln!("----- {path}: {} of {total} bytes, {} statements -----", report.bytes, report.statements);
+        }
+        true
+    });
+    let closed = session.close();
+    report.status = match (result, stopped) {
+        (Err(err), _) => format!("{err}"),
+        (Ok(()), Some(stopped)) => stopped,
+        (Ok(()), None) if !closed.success() => format!("mysql finished with: {closed}"),
+        (Ok(()), None) if report.failed > 0 => "loaded with failures".into(),
+        (Ok(()), None) => "loaded".into(),
+    };
+    report
+}
+
+// loads the dump of source into another table and/or database, refusing to replace
+// a table which holds rows unless forced
+pub fn restore(dbe:&DatabaseEnv, path:&str, source:&str, database:Option<&str>, table:&str, force:bool, on_error:OnError)->LoadReport {
+    let mut target_env = dbe.clone();
+    if let Some(database) = database {
+        if !exe_sql(dbe, &format!("CREATE DATABASE IF NOT EXISTS {}", quote_ident(database))).success() {
+            return LoadReport::refused(path, format!("failed to create database {database}"));
+        }
+        target_env.database = database.to_string();
+    }
+    if !force && table_exists(&target_env, table) && !is_empty(&target_env, table) {
+        return LoadReport::refused(path, format!("{}.{table} is not empty, use --force", target_env.database));
+    }
+    let options = LoadOptions {
+        on_error,
+        target: (source != table).then(|| (source.to_string(), quote_ident(table))),
+    };
+    load_file(&target_env, path, &options)
+}
+
+impl LoadReport {
+    fn refused(path:&str, status:String)->Self {
+        Self {
+            file: path.to_string(),
+            statements: 0,
+            failed: 0,
+            bytes: 0,
+            status,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statements(input:&str)->Vec<(String, String)> {
+        let mut out = Vec::new();
+        split(input.as_bytes(), |s| {
+            out.push((s.sql, s.delimiter));
+            true
+        }).unwrap();
+        out
+    }
+
+    #[test]
+    fn split_statements() {
+        let input = "-- comment; here\n\
+            /*!40101 SET NAMES utf8 */;\n\
+            /* dropped; */INSERT INTO `t` VALUES (1,'a;b\\'c'),(2,\"x -- y\"); # tail\n\
+            DELIMITER ;;\n\
+            CREATE TRIGGER tr BEFORE INSERT ON t FOR EACH ROW BEGIN SET @a=1; END;;\n\
+            DELIMITER ;\n\
+            SELECT 1";
+        assert_eq!(statements(input), vec![
+            ("/*!40101 SET NAMES utf8 */".into(), ";".into()),
+            ("INSERT INTO `t` VALUES (1,'a;b\\'c'),(2,\"x -- y\")".into(), ";".into()),
+            ("CREATE TRIGGER tr BEFORE INSERT ON t FOR EACH ROW BEGIN SET @a=1; END".into(), ";;".into()),
+            ("SELECT 1".into(), ";".into()),
+        ]);
+    }
+
+    #[test]
+    fn retarget_table() {
+        assert_eq!(retarget("INSERT INTO `sales1701` VALUES ('sales1701')", "sales1701", "`sales1701_old`"),
+            "INSERT INTO `sales1701_old` VALUES ('sales1701')");
+        assert_eq!(retarget("DROP TABLE IF EXISTS sales1701", "sales1701", "`db`.`s`"),
+            "DROP TABLE IF EXISTS `db`.`s`");
+        assert_eq!(retarget("INSERT INTO sales17011 VALUES (1)", "sales1701", "x"),
+            "INSERT INTO sales17011 VALUES (1)");
+    }
+}
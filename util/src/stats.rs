@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::exe_sql_with_output;
+use super::panelenv::{DatabaseEnv, TableRule};
+use super::report::{opt, write_reports, OutputFormat, Row};
+
+// the storage figures of information_schema.TABLES, rows are the engine's estimate
+#[derive(Debug, Default, serde::Serialize)]
+pub struct TableStats {
+    pub name: String,
+    pub year: String,
+    pub month: String,
+    pub table: String,
+    pub exists: bool,
+    pub rows: Option<u64>,
+    pub data_length: Option<u64>,
+    pub index_length: Option<u64>,
+    pub data_free: Option<u64>,
+    pub engine: Option<String>,
+    pub row_format: Option<String>,
+    pub collation: Option<String>,
+    pub create_time: Option<String>,
+    pub update_time: Option<String>,
+}
+
+// sums over the existing tables of one name or one year
+#[derive(Debug, Default, serde::Serialize)]
+pub struct StatsTotal {
+    pub by: String,
+    pub key: String,
+    pub tables: u64,
+    pub rows: u64,
+    pub data_length: u64,
+    pub index_length: u64,
+    pub data_free: u64,
+}
+
+impl Row for TableStats {
+    const COLUMNS: &'static [&'static str] = &["name", "year", "month", "table", "exists", "rows",
+        "data_length", "index_length", "data_free", "engine", "row_format", "collation", "create_time", "update_time"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.name.clone(),
+            self.year.clone(),
+            self.month.clone(),
+            self.table.clone(),
+            self.exists.to_string(),
+            opt(self.rows),
+            opt(self.data_length),
+            opt(self.index_length),
+            opt(self.data_free),
+            opt(self.engine.as_ref()),
+            opt(self.row_format.as_ref()),
+            opt(self.collation.as_ref()),
+            opt(self.create_time.as_ref()),
+            opt(self.update_time.as_ref()),
+        ]
+    }
+}
+
+impl Row for StatsTotal {
+    const COLUMNS: &'static [&'static str] = &["by", "key", "tables", "rows", "data_length", "index_length", "data_free"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.by.clone(),
+            self.key.clone(),
+            self.tables.to_string(),
+            self.rows.to_string(),
+            self.data_length.to_string(),
+            self.index_length.to_string(),
+            self.data_free.to_string(),
+        ]
+    }
+}
+
+// one query for the whole schema, then matched against the rule
+pub fn collect(dbe:&DatabaseEnv, rule:&TableRule, postfix:&str)->Vec<TableStats> {
+    let sql = "SELECT TABLE_NAME, TABLE_ROWS, DATA_LENGTH, INDEX_LENGTH, DATA_FREE, ENGINE, ROW_FORMAT, \
+        TABLE_COLLATION, CREATE_TIME, UPDATE_TIME FROM information_schema.TABLES WHERE TABLE_SCHEMA=DATABASE()";
+    let output = exe_sql_with_output(dbe, sql);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let live = stdout.lines()
+        .filter_map(|line| {
+            let cols = line.split('\t').collect::<Vec<_>>();
+            (cols.len() == 10).then(|| (cols[0].to_string(), cols))
+        })
+        .collect::<HashMap<_, _>>();
+    let text = |v:&str| (v != "NULL").then(|| v.to_string());
+    let num = |v:&str| v.parse::<u64>().ok();
+    rule.parts().into_iter()
+        .map(|(name, year, month)| {
+            let table = format!("{name}{year}{month}{postfix}");
+            let mut stats = TableStats {
+                name: name.into(),
+                year: year.into(),
+                month: month.into(),
+                ..Default::default()
+            };
+            if let Some(cols) = live.get(&table) {
+                stats.exists = true;
+                stats.rows = num(cols[1]);
+                stats.data_length = num(cols[2]);
+                stats.index_length = num(cols[3]);
+                stats.data_free = num(cols[4]);
+                stats.engine = text(cols[5]);
+                stats.row_format = text(cols[6]);
+                stats.collation = text(cols[7]);
+                stats.create_time = text(cols[8]);
+                stats.update_time = text(cols[9]);
+            }
+            stats.table = table;
+            stats
+        })
+        .collect()
+}
+
+pub fn totals(stats:&[TableStats])->Vec<StatsTotal> {
+    let mut totals: Vec<StatsTotal> = Vec::new();
+    for by in ["name", "year"] {
+        for s in stats.iter().filter(|s| s.exists) {
+            let key = if by == "name" { s.name.clone() } else { s.year.clone() };
+            let total = match totals.iter().position(|t| t.by == by && t.key == key) {
+                Some(i) => &mut totals[i],
+                None => {
+                    totals.push(StatsTotal { by: by.into(), key, ..Default::default() });
+                    totals.last_mut().unwrap()
+                }
+            };
+            total.tables += 1;
+            total.rows += s.rows.unwrap_or(0);
+            total.data_length += s.data_length.unwrap_or(0);
+            total.index_length += s.index_length.unwrap_or(0);
+            total.data_free += s.data_free.unwrap_or(0);
+        }
+    }
+    totals
+}
+
+pub fn write_stats(format:OutputFormat, stats:&[TableStats], totals:&[StatsTotal], out:&mut impl Write)->std::io::Result<()> {
+    if format == OutputFormat::Json {
+        let value = serde_json::json!({ "tables": stats, "totals": totals });
+        serde_json::to_writer_pretty(&mut *out, &value)?;
+        return writeln!(out);
+    }
+    write_reports(format, stats, out)?;
+    writeln!(out)?;
+    write_reports(format, totals, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn totals_by_name_and_year() {
+        let stat = |name:&str, year:&str, rows| TableStats {
+            name: name.into(),
+            year: year.into(),
+            exists: true,
+            rows: Some(rows),
+            data_length: Some(rows * 10),
+            ..Default::default()
+        };
+        let stats = vec![stat("a", "17", 1), stat("a", "18", 2), stat("b", "17", 4),
+            TableStats { name: "b".into(), year: "18".into(), ..Default::default() }];
+        let totals = totals(&stats);
+        let get = |by:&str, key:&str| totals.iter().find(|t| t.by == by && t.key == key).unwrap();
+        assert_eq!(get("name", "a").rows, 3);
+        assert_eq!(get("name", "b").tables, 1);
+        assert_eq!(get("year", "17").data_length, 50);
+        assert_eq!(get("year", "18").rows, 2);
+        assert_eq!(totals.len(), 4);
+    }
+}
@@ -0,0 +1,107 @@
+use std::process::ExitStatus;
+
+use super::{exe_sql, exe_sql_with_output, exit_status, table_exists};
+use super::clock;
+use super::confirm::{Approval, ConfirmEnv};
+use super::panelenv::{DatabaseEnv, TrashEnv};
+
+// a dropped table lives in the trash database as {table}__{stamp}
+const SEP: &str = "__";
+
+pub struct TrashEntry {
+    pub trashed: String,
+    pub table: String,
+    pub stamp: String,
+    pub rows: String,
+}
+
+pub fn trash_name(table:&str, stamp:&str)->String {
+    format!("{table}{SEP}{stamp}")
+}
+
+pub fn split_trash_name(trashed:&str)->Option<(&str, &str)> {
+    let (table, stamp) = trashed.rsplit_once(SEP)?;
+    clock::from_stamp(stamp)?;
+    Some((table, stamp))
+}
+
+pub fn soft_drop_sql(dbe:&DatabaseEnv, trash:&TrashEnv, table:&str)->Option<String> {
+    let database = &dbe.database;
+    let trashdb = &trash.database;
+    let trashed = trash_name(table, &clock::now_stamp());
+    if trashed.len() > 64 {
+        eprintln!("----- {trashed} is longer than 64 chars, can not move {table} to trash -----");
+        return None;
+    }
+    Some(format!(
+        "CREATE DATABASE IF NOT EXISTS {trashdb}; RENAME TABLE {database}.{table} TO {trashdb}.{trashed};"))
+}
+
+pub fn soft_drop(dbe:&DatabaseEnv, trash:&TrashEnv, table:&str)->ExitStatus {
+    match soft_drop_sql(dbe, trash, table) {
+        Some(sql) => exe_sql(dbe, &sql),
+        None => exit_status(1),
+    }
+}
+
+pub fn list(dbe:&DatabaseEnv, trash:&TrashEnv)->Vec<TrashEntry> {
+    let sql = format!(
+        "SELECT TABLE_NAME, TABLE_ROWS FROM information_schema.TABLES WHERE TABLE_SCHEMA='{}' ORDER BY TABLE_NAME",
+        trash.database);
+    let output = exe_sql_with_output(dbe, &sql);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    stdout.lines()
+        .filter_map(|line| {
+            let mut cols = line.split('\t');
+            let trashed = cols.next()?;
+            let rows = cols.next().unwrap_or("NULL");
+            let (table, stamp) = split_trash_name(trashed)?;
+            Some(TrashEntry {
+                trashed: trashed.into(),
+                table: table.into(),
+                stamp: stamp.into(),
+                rows: rows.into(),
+            })
+        })
+        .collect()
+}
+
+// table is either the original name, then the latest trashed copy is restored,
+// or the full trashed name {table}__{stamp}
+pub fn restore(dbe:&DatabaseEnv, trash:&TrashEnv, table:&str)->ExitStatus {
+    let entries = list(dbe, trash);
+    let Some(entry) = entries.iter()
+        .filter(|e| e.trashed == table || e.table == table)
+        .max_by(|a, b| a.stamp.cmp(&b.stamp)) else {
+        eprintln!("----- {table} is not found in trash {} -----", trash.database);
+        return exit_status(1);
+    };
+    if table_exists(dbe, &entry.table) {
+        eprintln!("----- {} already exists in {}, restore refused -----", entry.table, dbe.database);
+        return exit_status(1);
+    }
+    let sql = format!("RENAME TABLE {}.{} TO {}.{};",
+        trash.database, entry.trashed, dbe.database, entry.table);
+    exe_sql(dbe, &sql)
+}
+
+pub fn purge(dbe:&DatabaseEnv, trash:&TrashEnv, older_than:u64, confirm:&ConfirmEnv, approval:&Approval)->ExitStatus {
+    let deadline = clock::now_secs().saturating_sub(older_than);
+    let expired = list(dbe, trash).into_iter()
+        .filter(|e| clock::from_stamp(&e.stamp).is_some_and(|t| t < deadline))
+        .map(|e| e.trashed)
+        .collect::<Vec<_>>();
+    if expired.is_empty() {
+        println!("----- nothing in trash {} to purge -----", trash.database);
+        return exit_status(0);
+    }
+    if !confirm.confirm_batch(&trash.database, "PURGE", &expired, approval) {
+        return exit_status(1);
+    }
+    let sql = expired.iter()
+        .map(|trashed| format!("DROP TABLE {}.{trashed}", trash.database))
+        .collect::<Vec<_>>()
+        .join("; ");
+    exe_sql(dbe, &sql)
+}
@@ -0,0 +1,78 @@
+use super::{exe_sql, table_exists};
+use super::panelenv::{DatabaseEnv, TableRule};
+use super::report::Row;
+
+#[derive(Debug, serde::Serialize)]
+pub struct ViewReport {
+    pub view: String,
+    pub tables: usize,
+    pub missing: String,
+    pub status: String,
+}
+
+impl Row for ViewReport {
+    const COLUMNS: &'static [&'static str] = &["view", "tables", "missing", "status"];
+    fn cells(&self)->Vec<String> {
+        vec![
+            self.view.clone(),
+            self.tables.to_string(),
+            self.missing.clone(),
+            self.status.clone(),
+        ]
+    }
+}
+
+pub fn view_sql(view:&str, tables:&[String])->String {
+    let union = tables.iter()
+        .map(|table| format!("SELECT * FROM {table}"))
+        .collect::<Vec<_>>()
+        .join(" UNION ALL ");
+    format!("CREATE OR REPLACE VIEW {view} AS {union}")
+}
+
+// the view is replaced by the union of the tables which exist, dropped when none does
+fn replace(dbe:&DatabaseEnv, view:String, tables:Vec<String>)->ViewReport {
+    let (present, missing): (Vec<_>, Vec<_>) = tables.into_iter().partition(|t| table_exists(dbe, t));
+    let (sql, done) = if present.is_empty() {
+        (format!("DROP VIEW IF EXISTS {view}"), "dropped")
+    } else {
+        (view_sql(&view, &present), "replaced")
+    };
+    let status = if exe_sql(dbe, &sql).success() { done } else { "failed" };
+    ViewReport {
+        view,
+        tables: present.len(),
+        missing: missing.join(" "),
+        status: status.into(),
+    }
+}
+
+// {name}{year}_all per name and year, and {name}_all over every year when all_years
+pub fn create(dbe:&DatabaseEnv, rule:&TableRule, all_years:bool)->Vec<ViewReport> {
+    let mut reports = Vec::new();
+    for name in &rule.names {
+        for year in &rule.years {
+            let tables = rule.months.iter().map(|month| format!("{name}{year}{month}")).collect();
+            reports.push(replace(dbe, format!("{name}{year}_all"), tables));
+        }
+        if all_years {
+            let tables = rule.years.iter()
+                .flat_map(|year| rule.months.iter().map(move |month| format!("{name}{year}{month}")))
+                .collect();
+            reports.push(replace(dbe, format!("{name}_all"), tables));
+        }
+    }
+    reports
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_view() {
+        let tables = vec!["sales1701".to_string(), "sales1703".to_string()];
+        assert_eq!(view_sql("sales17_all", &tables),
+            "CREATE OR REPLACE VIEW sales17_all AS SELECT * FROM sales1701 UNION ALL SELECT * FROM sales1703");
+    }
+}